@@ -0,0 +1,79 @@
+// Compares searches that allocate their state on every call with searches
+// that reuse a SearchWorkspace. Run with `cargo bench --bench search_workspace`.
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashMap, time::Instant};
+use tsp::algo::{
+    search_workspace::SearchWorkspace,
+    shortest_paths::{astar, astar_with_workspace, harvesine_heuristic, Graph},
+};
+use tsp::utils::coordinate::Coordinate;
+
+const SIDE: usize = 300;
+const QUERIES: usize = 200;
+
+// square grid of streets roughly 100m apart, weighted a bit above the
+// straight-line distance so the haversine heuristic stays admissible
+fn grid() -> (Graph, HashMap<usize, Coordinate>) {
+    let mut map = HashMap::new();
+    for row in 0..SIDE {
+        for col in 0..SIDE {
+            let id = row * SIDE + col;
+            let coordinate = Coordinate {
+                lat: 4.60 + row as f64 * 0.001,
+                lng: -74.08 + col as f64 * 0.001,
+                id,
+            };
+            map.insert(id, coordinate);
+        }
+    }
+    let mut g = Graph::new(SIDE * SIDE);
+    let mut rng = StdRng::seed_from_u64(1);
+    for row in 0..SIDE {
+        for col in 0..SIDE {
+            let u = row * SIDE + col;
+            let mut neighbours = vec![];
+            if col + 1 < SIDE {
+                neighbours.push(u + 1);
+            }
+            if row + 1 < SIDE {
+                neighbours.push(u + SIDE);
+            }
+            for v in neighbours {
+                let w = harvesine_heuristic(&map, u, v) * rng.gen_range(1.0..1.5);
+                g.add_edge(u, v, w);
+                g.add_edge(v, u, w);
+            }
+        }
+    }
+    (g, map)
+}
+
+fn main() {
+    let (g, map) = grid();
+    let mut rng = StdRng::seed_from_u64(2);
+    // short queries, like the ones between snapped candidates of nearby stops
+    let queries: Vec<(usize, usize)> = (0..QUERIES)
+        .map(|_| {
+            let src = rng.gen_range(0..g.edges.len());
+            let dest = (src + rng.gen_range(1..4) * SIDE + rng.gen_range(1..4)) % g.edges.len();
+            (src, dest)
+        })
+        .collect();
+
+    let start = Instant::now();
+    for &(src, dest) in queries.iter() {
+        let _ = astar(&g, &map, src, dest, &harvesine_heuristic);
+    }
+    let fresh = start.elapsed();
+
+    let mut workspace = SearchWorkspace::new(g.edges.len());
+    let start = Instant::now();
+    for &(src, dest) in queries.iter() {
+        let _ = astar_with_workspace(&mut workspace, &g, &map, src, dest, &harvesine_heuristic);
+    }
+    let reused = start.elapsed();
+
+    println!("{} A* queries on a {}x{} grid", QUERIES, SIDE, SIDE);
+    println!("fresh state per query: {:?} ({:?} per query)", fresh, fresh / QUERIES as u32);
+    println!("reused workspace:      {:?} ({:?} per query)", reused, reused / QUERIES as u32);
+}
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE trips DROP COLUMN profile;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE trips ADD COLUMN profile VARCHAR(20) NOT NULL DEFAULT 'car';
\ No newline at end of file
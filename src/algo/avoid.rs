@@ -0,0 +1,139 @@
+use crate::{
+    ds::rtree::{segments_intersect, RTree},
+    utils::coordinate::Coordinate,
+};
+use std::collections::HashSet;
+
+// Areas and road segments a single request has to stay off
+#[derive(Debug, Default)]
+pub struct Avoid {
+    pub areas: Vec<Vec<Coordinate>>,
+    // arcs that may not be used: the blocked segments and every arc with some
+    // part inside an area
+    pub blocked: HashSet<(usize, usize)>,
+}
+
+impl Avoid {
+    // a blocked segment is closed in both directions; the arcs crossing the
+    // areas are looked up in the segment tree of the network being routed on
+    pub fn new(
+        areas: &[Vec<Coordinate>],
+        blocked_segments: &[(usize, usize)],
+        tree: &RTree,
+    ) -> Self {
+        let mut blocked = HashSet::new();
+        for &(u, v) in blocked_segments {
+            blocked.insert((u, v));
+            blocked.insert((v, u));
+        }
+        for area in areas {
+            for segment in tree.intersecting_polygon(area) {
+                blocked.insert((segment.from.id, segment.to.id));
+            }
+        }
+        Self {
+            areas: areas.to_vec(),
+            blocked,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.areas.is_empty() && self.blocked.is_empty()
+    }
+
+    pub fn allows_coordinate(&self, coordinate: &Coordinate) -> bool {
+        !self
+            .areas
+            .iter()
+            .any(|area| point_in_polygon(coordinate, area))
+    }
+
+    // an arc is avoided when any part of it lies inside an area, including
+    // long arcs that cut across an area without ending in it
+    pub fn allows_edge(&self, u: usize, v: usize) -> bool {
+        !self.blocked.contains(&(u, v))
+    }
+}
+
+// whether some part of the segment from-to lies inside the polygon
+pub fn segment_in_polygon(from: &Coordinate, to: &Coordinate, polygon: &[Coordinate]) -> bool {
+    point_in_polygon(from, polygon)
+        || point_in_polygon(to, polygon)
+        || (0..polygon.len()).any(|i| {
+            let j = (i + 1) % polygon.len();
+            segments_intersect(from, to, &polygon[i], &polygon[j])
+        })
+}
+
+// ray casting with lng as x and lat as y, the polygon may be open or closed
+pub fn point_in_polygon(point: &Coordinate, polygon: &[Coordinate]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for i in 0..polygon.len() {
+        let (a, b) = (&polygon[i], &polygon[j]);
+        if (a.lat > point.lat) != (b.lat > point.lat)
+            && point.lng < (b.lng - a.lng) * (point.lat - a.lat) / (b.lat - a.lat) + a.lng
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ds::{graph::Graph, rtree::graph_segments};
+    use std::collections::HashMap;
+
+    fn coordinate(lat: f64, lng: f64) -> Coordinate {
+        Coordinate { lat, lng, id: 0 }
+    }
+
+    fn square() -> Vec<Coordinate> {
+        vec![
+            coordinate(0.0, 0.0),
+            coordinate(0.0, 2.0),
+            coordinate(2.0, 2.0),
+            coordinate(2.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn test_point_in_polygon() {
+        assert!(point_in_polygon(&coordinate(1.0, 1.0), &square()));
+        assert!(!point_in_polygon(&coordinate(3.0, 1.0), &square()));
+        assert!(!point_in_polygon(&coordinate(1.0, -0.5), &square()));
+        assert!(!point_in_polygon(&coordinate(1.0, 1.0), &[]));
+
+        let mut closed = square();
+        closed.push(coordinate(0.0, 0.0));
+        assert!(point_in_polygon(&coordinate(0.5, 1.5), &closed));
+    }
+
+    #[test]
+    fn test_avoid_edges() {
+        let map: HashMap<usize, Coordinate> =
+            [(1.0, 1.0), (3.0, 3.0), (1.0, -1.0), (1.0, 3.0), (3.0, -1.0)]
+                .into_iter()
+                .enumerate()
+                .map(|(id, (lat, lng))| (id, Coordinate { lat, lng, id }))
+                .collect();
+        let mut g = Graph::new(5);
+        for (u, v) in [(1, 0), (0, 1), (2, 3), (2, 4), (3, 1), (1, 4), (4, 1)] {
+            g.add_edge(u, v, 1.0);
+        }
+        let tree = RTree::new(graph_segments(&g, &map));
+        let avoid = Avoid::new(&[square()], &[(1, 4)], &tree);
+        assert!(!avoid.allows_edge(1, 0));
+        assert!(!avoid.allows_edge(0, 1));
+        // crosses the area without a node inside it
+        assert!(!avoid.allows_edge(2, 3));
+        assert!(avoid.allows_edge(2, 4));
+        assert!(avoid.allows_edge(3, 1));
+        assert!(!avoid.allows_edge(1, 4));
+        assert!(!avoid.allows_edge(4, 1));
+        assert!(Avoid::default().is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+use crate::algo::error::RoutingError;
+use crate::algo::shortest_paths::NodeInfo;
+use crate::ds::{
+    graph::{Graph, GraphFingerprint},
+    priority_queue::MinHeap,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+// witness searches give up after settling this many nodes, adding the shortcut anyway
+const WITNESS_SETTLE_LIMIT: usize = 500;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ChEdge {
+    pub node: usize,
+    pub weight: f64,
+    // contracted node this shortcut skips over, None for original arcs
+    pub middle: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContractionHierarchy {
+    pub rank: Vec<usize>,
+    // upward[u] holds the arcs u -> v with rank[v] > rank[u]
+    pub upward: Vec<Vec<ChEdge>>,
+    // downward[v] holds the arcs u -> v with rank[u] > rank[v], keyed by u
+    pub downward: Vec<Vec<ChEdge>>,
+    // graph the hierarchy was built from
+    pub fingerprint: GraphFingerprint,
+}
+
+// graph that is still being contracted, with parallel arcs merged
+struct Overlay {
+    out: Vec<HashMap<usize, (f64, Option<usize>)>>,
+    inc: Vec<HashMap<usize, (f64, Option<usize>)>>,
+    contracted_neighbours: Vec<usize>,
+}
+
+impl Overlay {
+    fn new(g: &Graph) -> Self {
+        let n = g.edges.len();
+        let mut out = vec![HashMap::new(); n];
+        let mut inc = vec![HashMap::new(); n];
+        for (u, neighbours) in g.edges.iter().enumerate() {
+            for (i, &v) in neighbours.iter().enumerate() {
+                if u == v {
+                    continue;
+                }
+                let w = g.weights[u][i];
+                let entry: &mut (f64, Option<usize>) = out[u].entry(v).or_insert((w, None));
+                if w < entry.0 {
+                    *entry = (w, None);
+                }
+                inc[v].insert(u, *entry);
+            }
+        }
+        Self {
+            out,
+            inc,
+            contracted_neighbours: vec![0; n],
+        }
+    }
+
+    fn add_arc(&mut self, u: usize, v: usize, w: f64, middle: Option<usize>) {
+        let better = match self.out[u].get(&v) {
+            Some(&(current, _)) => w < current,
+            None => true,
+        };
+        if better {
+            self.out[u].insert(v, (w, middle));
+            self.inc[v].insert(u, (w, middle));
+        }
+    }
+
+    // distances from src to every node reachable within max_cost, skipping `avoid`
+    fn witness_search(&self, src: usize, avoid: usize, max_cost: f64) -> HashMap<usize, f64> {
+        let mut dist = HashMap::new();
+        let mut q = MinHeap::new();
+        dist.insert(src, 0.0);
+        q.insert(NodeInfo {
+            distance: 0.0,
+            id: src,
+        });
+        let mut settled = 0;
+        while !q.is_empty() && settled < WITNESS_SETTLE_LIMIT {
+            let node = q.extract_min();
+            if node.distance > max_cost {
+                break;
+            }
+            settled += 1;
+            for (&neighbour, &(w, _)) in self.out[node.id].iter() {
+                if neighbour == avoid {
+                    continue;
+                }
+                let alt = node.distance + w;
+                if alt < *dist.get(&neighbour).unwrap_or(&f64::INFINITY) {
+                    dist.insert(neighbour, alt);
+                    if q.contains(neighbour) {
+                        q.change_priority(neighbour, alt);
+                    } else {
+                        q.insert(NodeInfo {
+                            distance: alt,
+                            id: neighbour,
+                        });
+                    }
+                }
+            }
+        }
+        dist
+    }
+
+    // shortcuts (u, w, cost) needed to keep distances when v is removed
+    fn shortcuts(&self, v: usize) -> Vec<(usize, usize, f64)> {
+        let mut shortcuts = vec![];
+        for (&u, &(w_in, _)) in self.inc[v].iter() {
+            if !self.out[v].keys().any(|&w| w != u) {
+                continue;
+            }
+            let max_cost = self.out[v]
+                .iter()
+                .filter(|(&w, _)| w != u)
+                .map(|(_, &(w_out, _))| w_in + w_out)
+                .fold(0.0, f64::max);
+            let witness = self.witness_search(u, v, max_cost);
+            for (&w, &(w_out, _)) in self.out[v].iter() {
+                if w == u {
+                    continue;
+                }
+                let via = w_in + w_out;
+                if *witness.get(&w).unwrap_or(&f64::INFINITY) > via {
+                    shortcuts.push((u, w, via));
+                }
+            }
+        }
+        shortcuts
+    }
+
+    // edge difference plus the number of already contracted neighbours,
+    // which spreads the contraction evenly over the graph
+    fn importance(&self, v: usize) -> f64 {
+        let removed = self.inc[v].len() + self.out[v].len();
+        self.shortcuts(v).len() as f64 - removed as f64 + self.contracted_neighbours[v] as f64
+    }
+}
+
+impl ContractionHierarchy {
+    pub fn new(g: &Graph) -> Self {
+        let n = g.edges.len();
+        let mut overlay = Overlay::new(g);
+        let mut rank = vec![0; n];
+        let mut upward = vec![vec![]; n];
+        let mut downward = vec![vec![]; n];
+
+        let mut q = MinHeap::new();
+        for v in 0..n {
+            q.insert(NodeInfo {
+                distance: overlay.importance(v),
+                id: v,
+            });
+        }
+
+        let mut order = 0;
+        while !q.is_empty() {
+            let node = q.extract_min();
+            // lazy update: priorities go stale as neighbours get contracted
+            let importance = overlay.importance(node.id);
+            if !q.is_empty() && importance > q.get_min().distance {
+                q.insert(NodeInfo {
+                    distance: importance,
+                    id: node.id,
+                });
+                continue;
+            }
+
+            let v = node.id;
+            let shortcuts = overlay.shortcuts(v);
+            for (&w, &(weight, middle)) in overlay.out[v].iter() {
+                upward[v].push(ChEdge {
+                    node: w,
+                    weight,
+                    middle,
+                });
+            }
+            for (&u, &(weight, middle)) in overlay.inc[v].iter() {
+                downward[v].push(ChEdge {
+                    node: u,
+                    weight,
+                    middle,
+                });
+            }
+
+            let out = std::mem::take(&mut overlay.out[v]);
+            let inc = std::mem::take(&mut overlay.inc[v]);
+            for &w in out.keys() {
+                overlay.inc[w].remove(&v);
+                overlay.contracted_neighbours[w] += 1;
+            }
+            for &u in inc.keys() {
+                overlay.out[u].remove(&v);
+                overlay.contracted_neighbours[u] += 1;
+            }
+            for (u, w, cost) in shortcuts {
+                overlay.add_arc(u, w, cost, Some(v));
+            }
+
+            rank[v] = order;
+            order += 1;
+        }
+
+        Self {
+            rank,
+            upward,
+            downward,
+            fingerprint: g.fingerprint(),
+        }
+    }
+
+    pub fn shortest_path(
+        &self,
+        src: usize,
+        dest: usize,
+    ) -> Result<(f64, Vec<usize>), RoutingError> {
+        if src >= self.rank.len() {
+            return Err(RoutingError::NodeOutOfRange(src));
+        }
+        if dest >= self.rank.len() {
+            return Err(RoutingError::NodeOutOfRange(dest));
+        }
+
+        // prev_f[v] = (u, edge) for the upward arc u -> v reaching v,
+        // next_b[u] = (v, edge) for the arc u -> v leading towards dest
+        let mut dist_f: HashMap<usize, f64> = HashMap::new();
+        let mut dist_b: HashMap<usize, f64> = HashMap::new();
+        let mut prev_f: HashMap<usize, (usize, ChEdge)> = HashMap::new();
+        let mut next_b: HashMap<usize, (usize, ChEdge)> = HashMap::new();
+        let mut q_f = MinHeap::new();
+        let mut q_b = MinHeap::new();
+
+        dist_f.insert(src, 0.0);
+        dist_b.insert(dest, 0.0);
+        q_f.insert(NodeInfo {
+            distance: 0.0,
+            id: src,
+        });
+        q_b.insert(NodeInfo {
+            distance: 0.0,
+            id: dest,
+        });
+
+        let mut best = if src == dest { 0.0 } else { f64::INFINITY };
+        let mut meeting = src;
+
+        loop {
+            let forward_done = q_f.is_empty() || q_f.get_min().distance >= best;
+            let backward_done = q_b.is_empty() || q_b.get_min().distance >= best;
+            if forward_done && backward_done {
+                break;
+            }
+
+            let forward = !forward_done
+                && (backward_done || q_f.get_min().distance <= q_b.get_min().distance);
+            let (q, dist, other, arcs) = if forward {
+                (&mut q_f, &mut dist_f, &dist_b, &self.upward)
+            } else {
+                (&mut q_b, &mut dist_b, &dist_f, &self.downward)
+            };
+
+            let node = q.extract_min();
+            for edge in arcs[node.id].iter() {
+                let alt = node.distance + edge.weight;
+                if alt >= *dist.get(&edge.node).unwrap_or(&f64::INFINITY) {
+                    continue;
+                }
+                dist.insert(edge.node, alt);
+                if q.contains(edge.node) {
+                    q.change_priority(edge.node, alt);
+                } else {
+                    q.insert(NodeInfo {
+                        distance: alt,
+                        id: edge.node,
+                    });
+                }
+                if forward {
+                    prev_f.insert(edge.node, (node.id, *edge));
+                } else {
+                    next_b.insert(edge.node, (node.id, *edge));
+                }
+                if let Some(&d) = other.get(&edge.node) {
+                    if alt + d < best {
+                        best = alt + d;
+                        meeting = edge.node;
+                    }
+                }
+            }
+        }
+
+        if best == f64::INFINITY {
+            return Err(RoutingError::NoPath);
+        }
+
+        let mut hops = vec![];
+        let mut current = meeting;
+        while let Some(&(u, edge)) = prev_f.get(&current) {
+            hops.push((u, current, edge.middle));
+            current = u;
+        }
+        hops.reverse();
+        current = meeting;
+        while let Some(&(v, edge)) = next_b.get(&current) {
+            hops.push((current, v, edge.middle));
+            current = v;
+        }
+
+        let mut path = vec![src];
+        for (u, v, middle) in hops {
+            self.unpack(u, v, middle, &mut path);
+        }
+        Ok((best, path))
+    }
+
+    // appends the original nodes after u up to and including v
+    fn unpack(&self, u: usize, v: usize, middle: Option<usize>, path: &mut Vec<usize>) {
+        let mut stack = vec![(u, v, middle)];
+        while let Some((u, v, middle)) = stack.pop() {
+            match middle {
+                None => path.push(v),
+                Some(m) => {
+                    stack.push((m, v, self.arc(m, v).middle));
+                    stack.push((u, m, self.arc(u, m).middle));
+                }
+            }
+        }
+    }
+
+    // every arc of the hierarchy is stored at its lower ranked endpoint
+    fn arc(&self, u: usize, v: usize) -> ChEdge {
+        if self.rank[u] < self.rank[v] {
+            *self.upward[u].iter().find(|e| e.node == v).unwrap()
+        } else {
+            *self.downward[v].iter().find(|e| e.node == u).unwrap()
+        }
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(file)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(file: &str) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(file)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::shortest_paths::{dijkstra, reconstruct_path};
+    use crate::utils::create_adjacency_list_from_files;
+    use dotenvy::dotenv;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::{env, time::Instant};
+
+    fn random_graph(n: usize, m: usize, seed: u64) -> Graph {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut g = Graph::new(n);
+        for _ in 0..m {
+            let u = rng.gen_range(0..n);
+            let v = rng.gen_range(0..n);
+            g.add_edge(u, v, rng.gen_range(1..100) as f64);
+        }
+        g
+    }
+
+    fn path_cost(g: &Graph, path: &[usize]) -> f64 {
+        let mut cost = 0.0;
+        for pair in path.windows(2) {
+            cost += g.edges[pair[0]]
+                .iter()
+                .enumerate()
+                .filter(|(_, &v)| v == pair[1])
+                .map(|(i, _)| g.weights[pair[0]][i])
+                .fold(f64::INFINITY, f64::min);
+        }
+        cost
+    }
+
+    #[test]
+    fn test_contraction_hierarchy_small() {
+        let mut g = Graph::new(5);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(0, 2, 2.0);
+        g.add_edge(1, 2, 1.5);
+        g.add_edge(1, 3, 3.0);
+        g.add_edge(2, 3, 1.0);
+        g.add_edge(2, 4, 2.5);
+        g.add_edge(3, 4, 1.0);
+        let ch = ContractionHierarchy::new(&g);
+        let (cost, path) = ch.shortest_path(0, 4).unwrap();
+        let prev = dijkstra(&g, 0, 4).unwrap();
+        assert_eq!(cost, prev.0);
+        assert_eq!(path, reconstruct_path(prev.1, 4).unwrap());
+        assert!(ch.shortest_path(4, 0).is_err());
+    }
+
+    #[test]
+    fn test_contraction_hierarchy_matches_dijkstra() {
+        let g = random_graph(60, 240, 7);
+        let ch = ContractionHierarchy::new(&g);
+        for src in 0..g.edges.len() {
+            for dest in 0..g.edges.len() {
+                match dijkstra(&g, src, dest) {
+                    Ok((cost, _)) => {
+                        let (ch_cost, path) = ch.shortest_path(src, dest).unwrap();
+                        assert_eq!(ch_cost, cost);
+                        assert_eq!(path[0], src);
+                        assert_eq!(*path.last().unwrap(), dest);
+                        assert_eq!(path_cost(&g, &path), cost);
+                    }
+                    Err(_) => assert!(ch.shortest_path(src, dest).is_err()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_contraction_hierarchy_serialization() {
+        let g = random_graph(30, 90, 11);
+        let ch = ContractionHierarchy::new(&g);
+        let json = serde_json::to_string(&ch).unwrap();
+        let loaded: ContractionHierarchy = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.fingerprint, g.fingerprint());
+        for dest in 0..g.edges.len() {
+            let expected = ch.shortest_path(0, dest).ok();
+            let result = loaded.shortest_path(0, dest).ok();
+            assert_eq!(expected.map(|r| r.1), result.map(|r| r.1));
+        }
+    }
+
+    #[test]
+    #[ignore = "performance test"]
+    fn compare_contraction_hierarchy_and_dijkstra_running_times() {
+        dotenv().ok();
+        let coordinates_file = env::var("COORDINATES_FILE").unwrap();
+        let arcs_file = env::var("ARCS_FILE").unwrap();
+        let g = create_adjacency_list_from_files(&coordinates_file, &arcs_file).unwrap();
+
+        let start = Instant::now();
+        let ch = ContractionHierarchy::new(&g);
+        println!("Preprocessing time: {:?}", start.elapsed());
+
+        let src = rand::thread_rng().gen_range(0..g.edges.len());
+        let dest = rand::thread_rng().gen_range(0..g.edges.len());
+
+        let start = Instant::now();
+        let prev = dijkstra(&g, src, dest).unwrap();
+        println!("Dijkstra time: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let (cost, _path) = ch.shortest_path(src, dest).unwrap();
+        println!("CH query time: {:?}", start.elapsed());
+        assert!((cost - prev.0).abs() < 1e-6);
+    }
+}
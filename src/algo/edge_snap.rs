@@ -0,0 +1,173 @@
+use crate::algo::shortest_paths::{edge_weight, EdgeCost, Graph};
+use crate::ds::rtree::RTree;
+use crate::utils::coordinate::Coordinate;
+
+// A coordinate projected onto the arc from -> to. The route starts or ends
+// at point, a virtual node splitting the arc at fraction.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeSnap {
+    pub from: usize,
+    pub to: usize,
+    pub fraction: f64,
+    pub point: Coordinate,
+    // metres between the coordinate and point
+    pub offset: f64,
+}
+
+// cost of the given share of the arc u -> v, None when there is no such arc
+// or it may not be used
+fn partial_cost(g: &Graph, edge_cost: &EdgeCost<'_>, u: usize, v: usize, share: f64) -> Option<f64> {
+    let w = edge_weight(g, u, v)?;
+    edge_cost(u, v, w).map(|cost| cost * share)
+}
+
+impl EdgeSnap {
+    // nodes a route from the snapped point can leave through, with the cost
+    // of getting to them
+    pub fn exits(&self, g: &Graph, edge_cost: &EdgeCost<'_>) -> Vec<(usize, f64)> {
+        let mut exits = vec![];
+        if let Some(cost) = partial_cost(g, edge_cost, self.from, self.to, 1.0 - self.fraction) {
+            exits.push((self.to, cost));
+        }
+        if let Some(cost) = partial_cost(g, edge_cost, self.to, self.from, self.fraction) {
+            exits.push((self.from, cost));
+        }
+        exits
+    }
+
+    // nodes a route to the snapped point can arrive through, with the cost of
+    // getting from them to the point
+    pub fn entries(&self, g: &Graph, edge_cost: &EdgeCost<'_>) -> Vec<(usize, f64)> {
+        let mut entries = vec![];
+        if let Some(cost) = partial_cost(g, edge_cost, self.from, self.to, self.fraction) {
+            entries.push((self.from, cost));
+        }
+        if let Some(cost) = partial_cost(g, edge_cost, self.to, self.from, 1.0 - self.fraction) {
+            entries.push((self.to, cost));
+        }
+        entries
+    }
+
+    // end of the road opposite to node, which must be one of its ends
+    pub fn other_end(&self, node: usize) -> usize {
+        if node == self.to {
+            self.from
+        } else {
+            self.to
+        }
+    }
+
+    // cost of going straight along the road to other when both points lie on
+    // it, without going through any node
+    pub fn along(&self, other: &EdgeSnap, g: &Graph, edge_cost: &EdgeCost<'_>) -> Option<f64> {
+        let position = if (other.from, other.to) == (self.from, self.to) {
+            other.fraction
+        } else if (other.from, other.to) == (self.to, self.from) {
+            1.0 - other.fraction
+        } else {
+            return None;
+        };
+        if position >= self.fraction {
+            partial_cost(g, edge_cost, self.from, self.to, position - self.fraction)
+        } else {
+            partial_cost(g, edge_cost, self.to, self.from, self.fraction - position)
+        }
+    }
+}
+
+// closest allowed arc to coordinate, looked up in the segment tree of the
+// network the arcs belong to
+pub fn snap_to_edge(
+    tree: &RTree,
+    coordinate: &Coordinate,
+    allowed: &dyn Fn(usize, usize) -> bool,
+) -> Option<EdgeSnap> {
+    let nearest = tree.nearest_segment(coordinate, &|segment| {
+        allowed(segment.from.id, segment.to.id)
+    })?;
+    let (a, b) = (&nearest.segment.from, &nearest.segment.to);
+    let fraction = nearest.fraction;
+    let point = Coordinate {
+        lat: a.lat + fraction * (b.lat - a.lat),
+        lng: a.lng + fraction * (b.lng - a.lng),
+        // the node the point is closest to
+        id: if fraction < 0.5 { a.id } else { b.id },
+    };
+    Some(EdgeSnap {
+        from: a.id,
+        to: b.id,
+        fraction,
+        point,
+        offset: nearest.distance,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ds::rtree::graph_segments;
+    use std::collections::HashMap;
+
+    // 0 -> 1 one way, 1 <-> 2 both ways, about 1.1km each along the equator
+    fn road() -> (Graph, HashMap<usize, Coordinate>) {
+        let mut map = HashMap::new();
+        for i in 0..3 {
+            map.insert(i, Coordinate { lat: 0.0, lng: i as f64 * 0.01, id: i });
+        }
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 100.0);
+        g.add_edge(1, 2, 100.0);
+        g.add_edge(2, 1, 100.0);
+        (g, map)
+    }
+
+    #[test]
+    fn test_snap_to_edge() {
+        let (g, map) = road();
+        let tree = RTree::new(graph_segments(&g, &map));
+        // a quarter of the way from 1 to 2, 111 m north of the road
+        let coordinate = Coordinate { lat: 0.001, lng: 0.0125, id: 0 };
+        let snap = snap_to_edge(&tree, &coordinate, &|u, _| u != 2).unwrap();
+        assert_eq!((snap.from, snap.to), (1, 2));
+        assert!((snap.fraction - 0.25).abs() < 1e-9);
+        assert!((snap.offset - 111.2).abs() < 0.5);
+        assert!((snap.point.lng - 0.0125).abs() < 1e-9);
+        assert_eq!(snap.point.lat, 0.0);
+
+        let snap = snap_to_edge(&tree, &coordinate, &|u, _| u != 1).unwrap();
+        assert_eq!((snap.from, snap.to), (2, 1));
+        assert!((snap.fraction - 0.75).abs() < 1e-9);
+        // falls back to the one-way arc when the closer road is closed
+        let snap = snap_to_edge(&tree, &coordinate, &|u, v| (u, v) == (0, 1)).unwrap();
+        assert_eq!((snap.from, snap.to, snap.fraction), (0, 1, 1.0));
+        assert!(snap_to_edge(&tree, &coordinate, &|_, _| false).is_none());
+    }
+
+    #[test]
+    fn test_partial_costs() {
+        let (g, map) = road();
+        let cost = |_: usize, _: usize, w: f64| Some(w);
+        let snap = |from: usize, to: usize, fraction: f64| EdgeSnap {
+            from,
+            to,
+            fraction,
+            point: map[&from],
+            offset: 0.0,
+        };
+        // the one-way street can only be left forwards
+        let one_way = snap(0, 1, 0.25);
+        assert_eq!(one_way.exits(&g, &cost), vec![(1, 75.0)]);
+        assert_eq!(one_way.entries(&g, &cost), vec![(0, 25.0)]);
+        let two_way = snap(1, 2, 0.25);
+        assert_eq!(two_way.exits(&g, &cost), vec![(2, 75.0), (1, 25.0)]);
+        assert_eq!(two_way.entries(&g, &cost), vec![(1, 25.0), (2, 75.0)]);
+        // closed arcs are never used
+        assert_eq!(two_way.exits(&g, &|u, _, w| (u == 1).then_some(w)), vec![(2, 75.0)]);
+
+        assert_eq!(two_way.along(&snap(1, 2, 0.75), &g, &cost), Some(50.0));
+        assert_eq!(two_way.along(&snap(2, 1, 0.5), &g, &cost), Some(25.0));
+        assert_eq!(snap(1, 2, 0.75).along(&two_way, &g, &cost), Some(50.0));
+        assert_eq!(snap(0, 1, 0.75).along(&one_way, &g, &cost), None);
+        assert_eq!(one_way.along(&two_way, &g, &cost), None);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::utils::coordinate::Coordinate;
+use std::{collections::HashMap, error::Error};
+
+// Height above sea level of the nodes, in metres.
+#[derive(Debug, Default)]
+pub struct Elevations {
+    heights: HashMap<usize, f64>,
+}
+
+impl Elevations {
+    pub fn height(&self, node: usize) -> Option<f64> {
+        self.heights.get(&node).copied()
+    }
+
+    // metres gained going from u to v, 0 going down or when a height is unknown
+    pub fn climb(&self, u: usize, v: usize) -> f64 {
+        match (self.height(u), self.height(v)) {
+            (Some(from), Some(to)) => (to - from).max(0.0),
+            _ => 0.0,
+        }
+    }
+
+    // total ascent and descent along the points, looked up by node id
+    pub fn ascent_descent(&self, points: &[Coordinate]) -> (f64, f64) {
+        let mut ascent = 0.0;
+        let mut descent = 0.0;
+        for pair in points.windows(2) {
+            if let (Some(from), Some(to)) = (self.height(pair[0].id), self.height(pair[1].id)) {
+                if to > from {
+                    ascent += to - from;
+                } else {
+                    descent += from - to;
+                }
+            }
+        }
+        (ascent, descent)
+    }
+}
+
+// elevation file lines are `id elevation`, with the ids of the coordinates file
+pub fn parse_elevations(content: &str) -> Result<Elevations, Box<dyn Error>> {
+    let mut heights = HashMap::new();
+    for line in content.lines() {
+        let mut split_line = line.split_whitespace();
+        let Some(id) = split_line.next() else {
+            continue;
+        };
+        let id: usize = id.parse()?;
+        let height: f64 = split_line.next().ok_or("Missing elevation")?.parse()?;
+        heights.insert(id, height);
+    }
+    Ok(Elevations { heights })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascent_descent() {
+        let elevations = parse_elevations("0 2600\n1 2610.5\n\n2 2590\n").unwrap();
+        assert_eq!(elevations.climb(0, 1), 10.5);
+        assert_eq!(elevations.climb(1, 0), 0.0);
+        assert_eq!(elevations.climb(0, 3), 0.0);
+        let points: Vec<Coordinate> = [0, 1, 1, 2, 3, 0]
+            .iter()
+            .map(|&id| Coordinate { lat: 0.0, lng: 0.0, id })
+            .collect();
+        assert_eq!(elevations.ascent_descent(&points), (10.5, 20.5));
+        assert!(parse_elevations("0\n").is_err());
+    }
+}
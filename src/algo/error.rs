@@ -0,0 +1,112 @@
+use crate::utils::profile::RoutingProfile;
+use std::{error::Error, fmt};
+
+// Why a route could not be computed. Stops are indices into the locations of
+// the route, in the order they are visited.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoutingError {
+    // the two nodes are not connected
+    NoPath,
+    NodeOutOfRange(usize),
+    UnreachableLeg { from: usize, to: usize },
+    // no road node near the stop
+    SnapFailed { stop: usize },
+    TooManyStops { stops: usize, max: usize },
+    TooFewStops { stops: usize, min: usize },
+    InvalidCoordinate { stop: usize },
+    SolverTimeout,
+    ProfileUnavailable(RoutingProfile),
+    ElevationUnavailable,
+    // the penalty per metre climbed must be finite and not negative
+    InvalidClimbPenalty,
+    TooManySources { sources: usize, max: usize },
+    // sources times destinations of a matrix
+    TooManyEntries { max: usize },
+    TooManyAlternatives { alternatives: usize, max: usize },
+    // isochrone budget that is not positive or above the limits
+    InvalidBudget { max_time: f64, max_distance: f64 },
+    InvalidTrace { points: usize, max: usize },
+    // no route fits the trace, with the reason
+    MatchFailed(String),
+    // the GPS accuracy of a trace must be finite and above 0
+    InvalidSigma,
+}
+
+impl RoutingError {
+    // machine readable code sent along with the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            RoutingError::NoPath => "no_path",
+            RoutingError::NodeOutOfRange(_) => "node_out_of_range",
+            RoutingError::UnreachableLeg { .. } => "unreachable_leg",
+            RoutingError::SnapFailed { .. } => "snap_failed",
+            RoutingError::TooManyStops { .. } => "too_many_stops",
+            RoutingError::TooFewStops { .. } => "too_few_stops",
+            RoutingError::InvalidCoordinate { .. } => "invalid_coordinate",
+            RoutingError::SolverTimeout => "solver_timeout",
+            RoutingError::ProfileUnavailable(_) => "profile_unavailable",
+            RoutingError::ElevationUnavailable => "elevation_unavailable",
+            RoutingError::InvalidClimbPenalty => "invalid_climb_penalty",
+            RoutingError::TooManySources { .. } => "too_many_sources",
+            RoutingError::TooManyEntries { .. } => "too_many_entries",
+            RoutingError::TooManyAlternatives { .. } => "too_many_alternatives",
+            RoutingError::InvalidBudget { .. } => "invalid_budget",
+            RoutingError::InvalidTrace { .. } => "invalid_trace",
+            RoutingError::MatchFailed(_) => "match_failed",
+            RoutingError::InvalidSigma => "invalid_sigma",
+        }
+    }
+}
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoutingError::NoPath => write!(f, "No path found"),
+            RoutingError::NodeOutOfRange(node) => write!(f, "Node {} is out of range", node),
+            RoutingError::UnreachableLeg { from, to } => {
+                write!(f, "No path found from stop {} to stop {}", from, to)
+            }
+            RoutingError::SnapFailed { stop } => {
+                write!(f, "Could not snap stop {} to the road network", stop)
+            }
+            RoutingError::TooManyStops { stops, max } => {
+                write!(f, "{} stops given, at most {} are supported", stops, max)
+            }
+            RoutingError::TooFewStops { stops, min } => {
+                write!(f, "{} stops given, at least {} are needed", stops, min)
+            }
+            RoutingError::InvalidCoordinate { stop } => {
+                write!(f, "Stop {} has an invalid coordinate", stop)
+            }
+            RoutingError::SolverTimeout => write!(f, "The tour took too long to solve"),
+            RoutingError::ProfileUnavailable(profile) => {
+                write!(f, "Profile {} is not available", profile.as_str())
+            }
+            RoutingError::ElevationUnavailable => write!(f, "Elevation data is not available"),
+            RoutingError::InvalidClimbPenalty => {
+                write!(f, "The climb penalty must be a number not below 0")
+            }
+            RoutingError::TooManySources { sources, max } => {
+                write!(f, "{} sources given, at most {} are supported", sources, max)
+            }
+            RoutingError::TooManyEntries { max } => {
+                write!(f, "The matrix may have at most {} entries", max)
+            }
+            RoutingError::TooManyAlternatives { alternatives, max } => {
+                write!(f, "{} alternatives requested, at most {} are supported", alternatives, max)
+            }
+            RoutingError::InvalidBudget { max_time, max_distance } => write!(
+                f,
+                "The budget must be positive and at most {} seconds or {} metres",
+                max_time, max_distance
+            ),
+            RoutingError::InvalidTrace { points, max } => {
+                write!(f, "{} trace points given, between 1 and {} are supported", points, max)
+            }
+            RoutingError::MatchFailed(reason) => write!(f, "Could not match the trace: {}", reason),
+            RoutingError::InvalidSigma => write!(f, "The GPS accuracy must be a number above 0"),
+        }
+    }
+}
+
+impl Error for RoutingError {}
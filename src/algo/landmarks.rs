@@ -0,0 +1,291 @@
+use crate::algo::shortest_paths::{dijkstra_all, INFINITY};
+use crate::ds::graph::{Graph, GraphFingerprint};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum LandmarkSelection {
+    // each new landmark is the node farthest from the ones already chosen
+    Farthest,
+    // each new landmark is a leaf of the subtree where the current bounds are worst
+    Avoid,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Landmarks {
+    pub nodes: Vec<usize>,
+    // from_landmark[i][v] = d(nodes[i], v)
+    pub from_landmark: Vec<Vec<f64>>,
+    // to_landmark[i][v] = d(v, nodes[i])
+    pub to_landmark: Vec<Vec<f64>>,
+    // graph the tables were computed on
+    pub fingerprint: GraphFingerprint,
+}
+
+impl Landmarks {
+    pub fn new(g: &Graph, reverse: &Graph, count: usize, selection: LandmarkSelection) -> Self {
+        let mut landmarks = Self {
+            nodes: vec![],
+            from_landmark: vec![],
+            to_landmark: vec![],
+            fingerprint: g.fingerprint(),
+        };
+        let n = g.edges.len();
+        if n == 0 {
+            return landmarks;
+        }
+        for _ in 0..count.min(n) {
+            let candidate = match selection {
+                LandmarkSelection::Farthest => landmarks.farthest(g),
+                LandmarkSelection::Avoid => landmarks.avoid(g),
+            };
+            let Some(landmark) = candidate else { break };
+            landmarks.add(g, reverse, landmark);
+        }
+        landmarks
+    }
+
+    fn add(&mut self, g: &Graph, reverse: &Graph, landmark: usize) {
+        self.nodes.push(landmark);
+        self.from_landmark.push(dijkstra_all(g, landmark).0);
+        self.to_landmark.push(dijkstra_all(reverse, landmark).0);
+    }
+
+    fn farthest(&self, g: &Graph) -> Option<usize> {
+        let n = g.edges.len();
+        if self.nodes.is_empty() {
+            let (dist, _) = dijkstra_all(g, rand::thread_rng().gen_range(0..n));
+            return (0..n)
+                .filter(|&v| dist[v] < INFINITY)
+                .max_by(|&a, &b| dist[a].partial_cmp(&dist[b]).unwrap());
+        }
+        // unreachable nodes count as infinitely far, which spreads landmarks over components
+        (0..n).filter(|v| !self.nodes.contains(v)).max_by(|&a, &b| {
+            let da = self.closest_landmark_distance(a);
+            let db = self.closest_landmark_distance(b);
+            da.partial_cmp(&db).unwrap()
+        })
+    }
+
+    fn closest_landmark_distance(&self, v: usize) -> f64 {
+        self.from_landmark
+            .iter()
+            .map(|dist| dist[v])
+            .fold(INFINITY, f64::min)
+    }
+
+    fn avoid(&self, g: &Graph) -> Option<usize> {
+        let n = g.edges.len();
+        let root = rand::thread_rng().gen_range(0..n);
+        let (dist, prev) = dijkstra_all(g, root);
+
+        let mut children = vec![vec![]; n];
+        for (v, parent) in prev.iter().enumerate() {
+            if let Some(parent) = *parent {
+                children[parent].push(v);
+            }
+        }
+        // parents come before their children in this order
+        let mut order = vec![root];
+        let mut i = 0;
+        while i < order.len() {
+            order.extend(children[order[i]].iter().copied());
+            i += 1;
+        }
+
+        // size of a subtree is how badly the current landmarks bound it,
+        // or zero as soon as it already contains a landmark
+        let mut size = vec![0.0; n];
+        let mut has_landmark = vec![false; n];
+        for &v in order.iter().rev() {
+            has_landmark[v] =
+                self.nodes.contains(&v) || children[v].iter().any(|&child| has_landmark[child]);
+            if !has_landmark[v] {
+                size[v] = dist[v] - self.heuristic(root, v)
+                    + children[v].iter().map(|&child| size[child]).sum::<f64>();
+            }
+        }
+
+        let mut current = root;
+        if size[current] <= 0.0 {
+            return self.farthest(g);
+        }
+        while let Some(&next) = children[current]
+            .iter()
+            .max_by(|&&a, &&b| size[a].partial_cmp(&size[b]).unwrap())
+        {
+            if size[next] <= 0.0 {
+                break;
+            }
+            current = next;
+        }
+        Some(current)
+    }
+
+    // lower bound on d(v, t) from the triangle inequality over every landmark
+    pub fn heuristic(&self, v: usize, t: usize) -> f64 {
+        let mut best: f64 = 0.0;
+        for i in 0..self.nodes.len() {
+            let (from_v, from_t) = (self.from_landmark[i][v], self.from_landmark[i][t]);
+            if from_v < INFINITY && from_t < INFINITY {
+                best = best.max(from_t - from_v);
+            }
+            let (to_v, to_t) = (self.to_landmark[i][v], self.to_landmark[i][t]);
+            if to_v < INFINITY && to_t < INFINITY {
+                best = best.max(to_v - to_t);
+            }
+        }
+        best
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(file)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(file: &str) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(file)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    // checks that the tables were computed on g and hold a distance for each of its nodes
+    pub fn validate(&self, g: &Graph) -> Result<(), Box<dyn Error>> {
+        if self.fingerprint != g.fingerprint() {
+            return Err("the landmarks were computed for other arcs".into());
+        }
+        let n = g.edges.len();
+        let count = self.nodes.len();
+        let tables = self.from_landmark.iter().chain(self.to_landmark.iter());
+        if self.from_landmark.len() != count
+            || self.to_landmark.len() != count
+            || self.nodes.iter().any(|&landmark| landmark >= n)
+            || tables.clone().any(|table| table.len() != n)
+        {
+            return Err("the landmark tables do not match the graph".into());
+        }
+        if tables.flatten().any(|d| !d.is_finite() || *d < 0.0) {
+            return Err("the landmark tables hold invalid distances".into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::shortest_paths::{astar, dijkstra, reconstruct_path};
+    use crate::utils::{coordinate::Coordinate, create_adjacency_list_from_files};
+    use dotenvy::dotenv;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{collections::HashMap, env, time::Instant};
+
+    fn random_graph(n: usize, m: usize, seed: u64) -> Graph {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut g = Graph::new(n);
+        for _ in 0..m {
+            let u = rng.gen_range(0..n);
+            let v = rng.gen_range(0..n);
+            g.add_edge(u, v, rng.gen_range(1..100) as f64);
+        }
+        g
+    }
+
+    #[test]
+    fn test_landmark_heuristic_is_admissible() {
+        let g = random_graph(50, 200, 3);
+        let reverse = g.reverse();
+        for selection in [LandmarkSelection::Farthest, LandmarkSelection::Avoid] {
+            let landmarks = Landmarks::new(&g, &reverse, 4, selection);
+            assert_eq!(landmarks.nodes.len(), 4);
+            for v in 0..g.edges.len() {
+                let (dist, _) = dijkstra_all(&g, v);
+                for (t, &d) in dist.iter().enumerate() {
+                    assert!(landmarks.heuristic(v, t) <= d + 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_astar_with_landmarks() {
+        let g = random_graph(50, 200, 5);
+        let reverse = g.reverse();
+        let landmarks = Landmarks::new(&g, &reverse, 4, LandmarkSelection::Avoid);
+        let map: HashMap<usize, Coordinate> = HashMap::new();
+        let heuristic =
+            |_: &HashMap<usize, Coordinate>, v: usize, t: usize| landmarks.heuristic(v, t);
+        for src in 0..g.edges.len() {
+            for dest in 0..g.edges.len() {
+                let expected = dijkstra(&g, src, dest);
+                let result = astar(&g, &map, src, dest, &heuristic);
+                match expected {
+                    Ok((cost, _)) => {
+                        let (alt_cost, alt_prev) = result.unwrap();
+                        assert!((alt_cost - cost).abs() < 1e-9);
+                        let path = reconstruct_path(alt_prev, dest).unwrap();
+                        assert_eq!((path[0], *path.last().unwrap()), (src, dest));
+                    }
+                    Err(_) => assert!(result.is_err()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_landmarks_serialization() {
+        let g = random_graph(20, 60, 9);
+        let landmarks = Landmarks::new(&g, &g.reverse(), 3, LandmarkSelection::Farthest);
+        let json = serde_json::to_string(&landmarks).unwrap();
+        let loaded: Landmarks = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.nodes, landmarks.nodes);
+        assert_eq!(loaded.heuristic(0, 19), landmarks.heuristic(0, 19));
+    }
+
+    #[test]
+    fn test_landmarks_validate() {
+        let mut g = random_graph(20, 60, 13);
+        let mut landmarks = Landmarks::new(&g, &g.reverse(), 3, LandmarkSelection::Farthest);
+        assert!(landmarks.validate(&g).is_ok());
+        landmarks.to_landmark[1].pop();
+        assert!(landmarks.validate(&g).is_err());
+
+        let landmarks = Landmarks::new(&g, &g.reverse(), 3, LandmarkSelection::Farthest);
+        g.add_edge(0, 1, 1.0);
+        assert!(landmarks.validate(&g).is_err());
+    }
+
+    #[test]
+    #[ignore = "performance test"]
+    fn compare_haversine_and_landmark_astar_running_times() {
+        use crate::algo::shortest_paths::harvesine_heuristic;
+        use crate::utils::create_id_to_coordinates_hashmap_from_file;
+
+        dotenv().ok();
+        let coordinates_file = env::var("COORDINATES_FILE").unwrap();
+        let arcs_file = env::var("ARCS_FILE").unwrap();
+        let g = create_adjacency_list_from_files(&coordinates_file, &arcs_file).unwrap();
+        let map = create_id_to_coordinates_hashmap_from_file(&coordinates_file).unwrap();
+
+        let start = Instant::now();
+        let landmarks = Landmarks::new(&g, &g.reverse(), 16, LandmarkSelection::Avoid);
+        println!("Preprocessing time: {:?}", start.elapsed());
+
+        let src = rand::thread_rng().gen_range(0..g.edges.len());
+        let dest = rand::thread_rng().gen_range(0..g.edges.len());
+
+        let start = Instant::now();
+        let prev = astar(&g, &map, src, dest, &harvesine_heuristic).unwrap();
+        println!("A* (haversine) time: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let alt = astar(&g, &map, src, dest, &|_, v, t| landmarks.heuristic(v, t)).unwrap();
+        println!("A* (landmarks) time: {:?}", start.elapsed());
+        assert!((alt.0 - prev.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,443 @@
+use crate::ds::graph::Graph;
+use crate::utils::{coordinate::Coordinate, language::Language};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+// points closer than this are treated as the same place
+const SAME_POINT: f64 = 0.5;
+
+// Names of the streets and which arcs belong to roundabouts, looked up by arc.
+#[derive(Debug, Default)]
+pub struct StreetNames {
+    names: HashMap<(usize, usize), String>,
+    roundabouts: HashSet<(usize, usize)>,
+}
+
+impl StreetNames {
+    // names are shared by both directions of a street
+    pub fn name(&self, u: usize, v: usize) -> Option<&str> {
+        self.names
+            .get(&(u, v))
+            .or_else(|| self.names.get(&(v, u)))
+            .map(String::as_str)
+    }
+
+    pub fn is_roundabout(&self, u: usize, v: usize) -> bool {
+        self.roundabouts.contains(&(u, v))
+    }
+}
+
+// street names file lines are `from to kind name`, where kind is `street` or
+// `roundabout` and the name may have spaces or be left out
+pub fn parse_street_names(content: &str) -> Result<StreetNames, Box<dyn Error>> {
+    let mut streets = StreetNames::default();
+    for line in content.lines() {
+        let mut split_line = line.split_whitespace();
+        let Some(from) = split_line.next() else {
+            continue;
+        };
+        let from: usize = from.parse()?;
+        let to: usize = split_line.next().ok_or("Missing to node")?.parse()?;
+        match split_line.next() {
+            Some("street") => {}
+            Some("roundabout") => {
+                streets.roundabouts.insert((from, to));
+            }
+            _ => return Err(format!("Unknown street kind in line `{}`", line).into()),
+        }
+        let name = split_line.collect::<Vec<&str>>().join(" ");
+        if !name.is_empty() {
+            streets.names.insert((from, to), name);
+        }
+    }
+    Ok(streets)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManeuverKind {
+    Depart,
+    Continue,
+    SlightLeft,
+    Left,
+    SharpLeft,
+    SlightRight,
+    Right,
+    SharpRight,
+    UTurn,
+    ForkLeft,
+    ForkRight,
+    Roundabout,
+    Arrive,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Maneuver {
+    pub kind: ManeuverKind,
+    pub instruction: String,
+    pub location: Coordinate,
+    // street taken after the maneuver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub street: Option<String>,
+    // exit to take, for roundabouts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit: Option<usize>,
+    // compass bearing in degrees right after the maneuver
+    pub bearing: f64,
+    // metres to the next maneuver
+    pub distance: f64,
+}
+
+// initial compass bearing from a to b, in degrees clockwise from north
+pub fn bearing(a: &Coordinate, b: &Coordinate) -> f64 {
+    let (lat_a, lat_b) = (a.lat.to_radians(), b.lat.to_radians());
+    let delta = (b.lng - a.lng).to_radians();
+    let y = delta.sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * delta.cos();
+    (y.atan2(x).to_degrees() + 360.0) % 360.0
+}
+
+// change of heading at b when going a -> b -> c, negative to the left and
+// positive to the right
+pub fn turn_angle(a: &Coordinate, b: &Coordinate, c: &Coordinate) -> f64 {
+    (bearing(b, c) - bearing(a, b) + 540.0) % 360.0 - 180.0
+}
+
+fn classify(angle: f64) -> ManeuverKind {
+    let (left, kind) = (angle < 0.0, angle.abs());
+    match (left, kind) {
+        (_, a) if a < 20.0 => ManeuverKind::Continue,
+        (_, a) if a >= 170.0 => ManeuverKind::UTurn,
+        (true, a) if a < 45.0 => ManeuverKind::SlightLeft,
+        (true, a) if a < 135.0 => ManeuverKind::Left,
+        (true, _) => ManeuverKind::SharpLeft,
+        (false, a) if a < 45.0 => ManeuverKind::SlightRight,
+        (false, a) if a < 135.0 => ManeuverKind::Right,
+        (false, _) => ManeuverKind::SharpRight,
+    }
+}
+
+fn compass(bearing: f64, language: Language) -> &'static str {
+    let sector = ((bearing + 22.5) / 45.0) as usize % 8;
+    match language {
+        Language::En => ["north", "northeast", "east", "southeast", "south", "southwest", "west", "northwest"][sector],
+        Language::Es => ["norte", "noreste", "este", "sureste", "sur", "suroeste", "oeste", "noroeste"][sector],
+    }
+}
+
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (1, r) if r != 11 => "st",
+        (2, r) if r != 12 => "nd",
+        (3, r) if r != 13 => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+pub fn instruction(maneuver: &Maneuver, language: Language) -> String {
+    use ManeuverKind::*;
+    let exit = maneuver.exit.unwrap_or(1);
+    let (text, onto) = match language {
+        Language::En => {
+            let text = match maneuver.kind {
+                Depart => format!("Head {}", compass(maneuver.bearing, language)),
+                Continue => "Continue straight".to_string(),
+                SlightLeft => "Bear left".to_string(),
+                Left => "Turn left".to_string(),
+                SharpLeft => "Make a sharp left".to_string(),
+                SlightRight => "Bear right".to_string(),
+                Right => "Turn right".to_string(),
+                SharpRight => "Make a sharp right".to_string(),
+                UTurn => "Make a U-turn".to_string(),
+                ForkLeft => "Keep left at the fork".to_string(),
+                ForkRight => "Keep right at the fork".to_string(),
+                Roundabout => format!("At the roundabout, take the {} exit", ordinal(exit)),
+                Arrive => return "You have arrived at your destination".to_string(),
+            };
+            let onto = if maneuver.kind == Depart { "on" } else { "onto" };
+            (text, onto)
+        }
+        Language::Es => {
+            let text = match maneuver.kind {
+                Depart => format!("Dirígete al {}", compass(maneuver.bearing, language)),
+                Continue => "Continúa recto".to_string(),
+                SlightLeft => "Gira levemente a la izquierda".to_string(),
+                Left => "Gira a la izquierda".to_string(),
+                SharpLeft => "Gira bruscamente a la izquierda".to_string(),
+                SlightRight => "Gira levemente a la derecha".to_string(),
+                Right => "Gira a la derecha".to_string(),
+                SharpRight => "Gira bruscamente a la derecha".to_string(),
+                UTurn => "Haz un giro en U".to_string(),
+                ForkLeft => "Mantente a la izquierda en la bifurcación".to_string(),
+                ForkRight => "Mantente a la derecha en la bifurcación".to_string(),
+                Roundabout => format!("En la glorieta, toma la {}.ª salida", exit),
+                Arrive => return "Has llegado a tu destino".to_string(),
+            };
+            let onto = if matches!(maneuver.kind, Depart | Continue) { "por" } else { "hacia" };
+            (text, onto)
+        }
+    };
+    match &maneuver.street {
+        Some(street) => format!("{} {} {}", text, onto, street),
+        None => text,
+    }
+}
+
+// Walks the points of a route and emits a maneuver wherever the driver has a
+// choice to make or the street changes. Curves of a road without junctions are
+// not reported. Interior points must be nodes of g.
+pub fn maneuvers(
+    points: &[Coordinate],
+    g: &Graph,
+    map: &HashMap<usize, Coordinate>,
+    streets: &StreetNames,
+    language: Language,
+) -> Vec<Maneuver> {
+    let mut pts: Vec<Coordinate> = vec![];
+    for point in points {
+        if pts.last().is_none_or(|last| last.distance_to(point) >= SAME_POINT) {
+            pts.push(*point);
+        }
+    }
+    let n = pts.len();
+    if n < 2 {
+        return vec![];
+    }
+    let street = |i: usize| streets.name(pts[i].id, pts[i + 1].id).map(str::to_string);
+
+    // maneuvers with the index of the point where they happen
+    let mut found: Vec<(usize, ManeuverKind, Option<String>, Option<usize>)> = vec![];
+    found.push((0, ManeuverKind::Depart, street(0), None));
+    let mut current = street(0);
+    let mut i = 1;
+    while i < n - 1 {
+        let node = pts[i].id;
+        if streets.is_roundabout(node, pts[i + 1].id) {
+            // count the exits passed until the route leaves the ring at j
+            let mut exit = 1;
+            let mut j = i + 1;
+            while j < n - 1 && streets.is_roundabout(pts[j].id, pts[j + 1].id) {
+                let ring = pts[j].id;
+                if g.edges[ring].iter().any(|&v| !streets.is_roundabout(ring, v)) {
+                    exit += 1;
+                }
+                j += 1;
+            }
+            let after = if j < n - 1 { street(j) } else { None };
+            found.push((i, ManeuverKind::Roundabout, after.clone(), Some(exit)));
+            current = after;
+            i = j + 1;
+            continue;
+        }
+
+        let mut options: Vec<usize> = g.edges[node]
+            .iter()
+            .copied()
+            .filter(|&v| v != pts[i - 1].id)
+            .collect();
+        options.sort_unstable();
+        options.dedup();
+        let angle = turn_angle(&pts[i - 1], &pts[i], &pts[i + 1]);
+        // the other way closest to straight on, to tell forks from turns
+        let alternative = options
+            .iter()
+            .filter(|&&v| v != pts[i + 1].id)
+            .filter_map(|v| map.get(v))
+            .map(|c| turn_angle(&pts[i - 1], &pts[i], c))
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()));
+        let kind = match alternative {
+            Some(other) if angle.abs() < 45.0 && other.abs() < 45.0 => {
+                if angle < other {
+                    ManeuverKind::ForkLeft
+                } else {
+                    ManeuverKind::ForkRight
+                }
+            }
+            _ => classify(angle),
+        };
+        let next = street(i);
+        let renamed = next.is_some() && next != current;
+        let choice = alternative.is_some() && kind != ManeuverKind::Continue;
+        if choice || renamed || kind == ManeuverKind::UTurn {
+            found.push((i, kind, next.clone(), None));
+            current = next;
+        }
+        i += 1;
+    }
+    found.push((n - 1, ManeuverKind::Arrive, None, None));
+
+    let mut travelled = vec![0.0; n];
+    for k in 1..n {
+        travelled[k] = travelled[k - 1] + pts[k - 1].distance_to(&pts[k]);
+    }
+    (0..found.len())
+        .map(|m| {
+            let (at, kind, street, exit) = found[m].clone();
+            let next_at = found.get(m + 1).map_or(at, |next| next.0);
+            let bearing = if at < n - 1 {
+                bearing(&pts[at], &pts[at + 1])
+            } else {
+                bearing(&pts[at - 1], &pts[at])
+            };
+            let mut maneuver = Maneuver {
+                kind,
+                instruction: String::new(),
+                location: pts[at],
+                street,
+                exit,
+                bearing,
+                distance: travelled[next_at] - travelled[at],
+            };
+            maneuver.instruction = instruction(&maneuver, language);
+            maneuver
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(
+        coordinates: &[(f64, f64)],
+        arcs: &[(usize, usize)],
+        two_way: &[(usize, usize)],
+    ) -> (Graph, HashMap<usize, Coordinate>) {
+        let map: HashMap<usize, Coordinate> = coordinates
+            .iter()
+            .enumerate()
+            .map(|(id, &(lat, lng))| (id, Coordinate { lat, lng, id }))
+            .collect();
+        let mut g = Graph::new(coordinates.len());
+        for &(u, v) in arcs {
+            g.add_edge(u, v, 1.0);
+        }
+        for &(u, v) in two_way {
+            g.add_edge(u, v, 1.0);
+            g.add_edge(v, u, 1.0);
+        }
+        (g, map)
+    }
+
+    fn points(map: &HashMap<usize, Coordinate>, nodes: &[usize]) -> Vec<Coordinate> {
+        nodes.iter().map(|node| map[node]).collect()
+    }
+
+    fn kinds(maneuvers: &[Maneuver]) -> Vec<ManeuverKind> {
+        maneuvers.iter().map(|m| m.kind).collect()
+    }
+
+    #[test]
+    fn test_bearing_and_turn_angle() {
+        let c = |lat: f64, lng: f64| Coordinate { lat, lng, id: 0 };
+        assert!((bearing(&c(0.0, 0.0), &c(1.0, 0.0)) - 0.0).abs() < 1e-6);
+        assert!((bearing(&c(0.0, 0.0), &c(0.0, 1.0)) - 90.0).abs() < 1e-6);
+        assert!((bearing(&c(0.0, 0.0), &c(0.0, -1.0)) - 270.0).abs() < 1e-6);
+        let angle = turn_angle(&c(0.0, 0.0), &c(0.001, 0.0), &c(0.001, -0.001));
+        assert!((angle + 90.0).abs() < 0.1);
+        assert_eq!(classify(angle), ManeuverKind::Left);
+        assert_eq!(classify(175.0), ManeuverKind::UTurn);
+        assert_eq!(classify(30.0), ManeuverKind::SlightRight);
+    }
+
+    #[test]
+    fn test_turn_at_junction() {
+        // heading north on 0 -> 1, where the street meets an east-west one
+        let (g, map) = network(
+            &[(4.600, -74.08), (4.601, -74.08), (4.601, -74.081), (4.601, -74.079)],
+            &[],
+            &[(0, 1), (1, 2), (1, 3)],
+        );
+        let streets =
+            parse_street_names("0 1 street Carrera 7\n1 2 street Calle 26\n1 3 street Calle 26\n").unwrap();
+        let result = maneuvers(&points(&map, &[0, 1, 3]), &g, &map, &streets, Language::En);
+        assert_eq!(kinds(&result), vec![ManeuverKind::Depart, ManeuverKind::Right, ManeuverKind::Arrive]);
+        assert_eq!(result[0].instruction, "Head north on Carrera 7");
+        assert_eq!(result[1].instruction, "Turn right onto Calle 26");
+        assert_eq!(result[2].instruction, "You have arrived at your destination");
+        assert!((result[0].distance - 110.6).abs() < 1.0);
+        assert_eq!(result[2].distance, 0.0);
+
+        let result = maneuvers(&points(&map, &[0, 1, 2]), &g, &map, &streets, Language::Es);
+        assert_eq!(result[0].instruction, "Dirígete al norte por Carrera 7");
+        assert_eq!(result[1].instruction, "Gira a la izquierda hacia Calle 26");
+        assert_eq!(result[2].instruction, "Has llegado a tu destino");
+    }
+
+    #[test]
+    fn test_bends_without_junctions_are_skipped() {
+        let (g, map) = network(
+            &[(4.600, -74.08), (4.601, -74.08), (4.601, -74.079)],
+            &[],
+            &[(0, 1), (1, 2)],
+        );
+        let mut route = points(&map, &[0, 1, 2]);
+        // a snapped start on top of the first node adds nothing
+        route.insert(0, map[&0]);
+        let result = maneuvers(&route, &g, &map, &StreetNames::default(), Language::En);
+        assert_eq!(kinds(&result), vec![ManeuverKind::Depart, ManeuverKind::Arrive]);
+        assert!((result[0].distance - 221.4).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_fork() {
+        let (g, map) = network(
+            &[(4.600, -74.08), (4.601, -74.08), (4.602, -74.0803), (4.602, -74.0797)],
+            &[(0, 1), (1, 2), (1, 3)],
+            &[],
+        );
+        let streets = StreetNames::default();
+        let result = maneuvers(&points(&map, &[0, 1, 2]), &g, &map, &streets, Language::En);
+        assert_eq!(result[1].kind, ManeuverKind::ForkLeft);
+        assert_eq!(result[1].instruction, "Keep left at the fork");
+        let result = maneuvers(&points(&map, &[0, 1, 3]), &g, &map, &streets, Language::Es);
+        assert_eq!(result[1].instruction, "Mantente a la derecha en la bifurcación");
+    }
+
+    #[test]
+    fn test_roundabout() {
+        // ring 1 -> 2 -> 3 -> 4 -> 1 entered from 0, with a spoke leaving every other node
+        let (g, map) = network(
+            &[
+                (4.6000, -74.0800),
+                (4.6015, -74.0800),
+                (4.6020, -74.0795),
+                (4.6025, -74.0800),
+                (4.6020, -74.0805),
+                (4.6020, -74.0790),
+                (4.6030, -74.0800),
+                (4.6020, -74.0810),
+            ],
+            &[(1, 2), (2, 3), (3, 4), (4, 1)],
+            &[(0, 1), (2, 5), (3, 6), (4, 7)],
+        );
+        let streets = parse_street_names(
+            "1 2 roundabout\n2 3 roundabout\n3 4 roundabout\n4 1 roundabout\n3 6 street Avenida Boyacá\n",
+        )
+        .unwrap();
+        let route = points(&map, &[0, 1, 2, 3, 6]);
+        let result = maneuvers(&route, &g, &map, &streets, Language::En);
+        assert_eq!(kinds(&result), vec![ManeuverKind::Depart, ManeuverKind::Roundabout, ManeuverKind::Arrive]);
+        assert_eq!(result[1].exit, Some(2));
+        assert_eq!(result[1].instruction, "At the roundabout, take the 2nd exit onto Avenida Boyacá");
+        let result = maneuvers(&route, &g, &map, &streets, Language::Es);
+        assert_eq!(result[1].instruction, "En la glorieta, toma la 2.ª salida hacia Avenida Boyacá");
+    }
+
+    #[test]
+    fn test_parse_street_names() {
+        let streets = parse_street_names("0 1 street Calle 26\n\n1 2 roundabout\n").unwrap();
+        assert_eq!(streets.name(1, 0), Some("Calle 26"));
+        assert_eq!(streets.name(1, 2), None);
+        assert!(streets.is_roundabout(1, 2));
+        assert!(!streets.is_roundabout(2, 1));
+        assert!(parse_street_names("0 1 avenue Calle 26\n").is_err());
+        assert_eq!(ordinal(1), "1st");
+        assert_eq!(ordinal(12), "12th");
+        assert_eq!(ordinal(23), "23rd");
+    }
+}
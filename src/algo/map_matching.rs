@@ -0,0 +1,289 @@
+use crate::algo::search_workspace::SearchWorkspace;
+use crate::algo::shortest_paths::{one_to_many, Graph, Route};
+use crate::utils::coordinate::Coordinate;
+use std::{collections::HashMap, error::Error};
+
+// transitions are only searched up to this many times the straight-line
+// distance between two GPS points, and never less than MIN_SEARCH_RADIUS nor
+// more than MAX_SEARCH_RADIUS metres; points further apart start a new chain
+const MAX_DETOUR_FACTOR: f64 = 4.0;
+const MIN_SEARCH_RADIUS: f64 = 1000.0;
+const MAX_SEARCH_RADIUS: f64 = 20000.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MatchOptions {
+    // standard deviation of the GPS noise, in metres
+    pub sigma: f64,
+    // how much the route between two points may differ from the straight line
+    pub beta: f64,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            sigma: 10.0,
+            beta: 50.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MatchedRoute {
+    pub distance: f64,
+    // the matched path, which jumps where the trace could not be followed on the road
+    pub nodes: Vec<usize>,
+    // node each GPS point was matched to, None for points without candidates
+    pub matched: Vec<Option<usize>>,
+}
+
+// candidates of one GPS point and the best log-probability of ending there
+struct Layer {
+    point: usize,
+    candidates: Vec<usize>,
+    scores: Vec<f64>,
+    // candidate of the previous layer, None where a new chain starts
+    back: Vec<Option<usize>>,
+    // road route from the back candidate
+    routes: Vec<Option<Route>>,
+}
+
+impl Layer {
+    fn best(&self) -> usize {
+        (0..self.scores.len())
+            .max_by(|&a, &b| self.scores[a].total_cmp(&self.scores[b]))
+            .unwrap()
+    }
+}
+
+// Hidden Markov model map matching: the GPS points are noisy observations of
+// the nodes in candidates, points are likelier to come from close nodes and
+// consecutive nodes are likelier the closer their road distance is to the
+// straight-line distance between the points. Arc weights must be in metres.
+pub fn match_trace(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    map: &HashMap<usize, Coordinate>,
+    trace: &[Coordinate],
+    candidates: &[Vec<usize>],
+    options: &MatchOptions,
+) -> Result<MatchedRoute, Box<dyn Error>> {
+    let emission = |point: &Coordinate, node: usize| -> f64 {
+        -0.5 * (point.distance_to(&map[&node]) / options.sigma).powi(2)
+    };
+
+    let mut layers: Vec<Layer> = vec![];
+    for (point, nodes) in candidates.iter().enumerate() {
+        if nodes.is_empty() {
+            continue;
+        }
+        let observed: Vec<f64> = nodes.iter().map(|&node| emission(&trace[point], node)).collect();
+        let mut scores = vec![f64::NEG_INFINITY; nodes.len()];
+        let mut back = vec![None; nodes.len()];
+        let mut routes = vec![None; nodes.len()];
+        if let Some(previous) = layers.last() {
+            let straight = trace[previous.point].distance_to(&trace[point]);
+            let radius = (straight * MAX_DETOUR_FACTOR).clamp(MIN_SEARCH_RADIUS, MAX_SEARCH_RADIUS);
+            for (p, &from) in previous.candidates.iter().enumerate() {
+                if previous.scores[p] == f64::NEG_INFINITY {
+                    continue;
+                }
+                let costs = one_to_many(workspace, g, from, nodes, radius, &|_, _, w| Some(w));
+                for (q, cost) in costs.into_iter().enumerate() {
+                    let Some(cost) = cost else {
+                        continue;
+                    };
+                    let transition = -(cost - straight).abs() / options.beta;
+                    let score = previous.scores[p] + transition + observed[q];
+                    if score > scores[q] {
+                        scores[q] = score;
+                        back[q] = Some(p);
+                        // the search tree of one_to_many is still in the workspace
+                        routes[q] = Some((cost, workspace.forward.path_to(nodes[q])));
+                    }
+                }
+            }
+        }
+        // no candidate can be reached from the previous point: start a new chain
+        if back.iter().all(|b| b.is_none()) {
+            scores = observed;
+        }
+        layers.push(Layer {
+            point,
+            candidates: nodes.clone(),
+            scores,
+            back,
+            routes,
+        });
+    }
+    if layers.is_empty() {
+        return Err("No GPS point could be matched".into());
+    }
+
+    // Viterbi backtracking, jumping to the best end of the previous chain on breaks
+    let mut chosen = vec![0; layers.len()];
+    let mut current = layers[layers.len() - 1].best();
+    for l in (0..layers.len()).rev() {
+        chosen[l] = current;
+        if l > 0 {
+            current = match layers[l].back[current] {
+                Some(p) => p,
+                None => layers[l - 1].best(),
+            };
+        }
+    }
+
+    let mut matched = vec![None; trace.len()];
+    let mut nodes = vec![];
+    let mut total = 0.0;
+    for (l, layer) in layers.iter().enumerate() {
+        let node = layer.candidates[chosen[l]];
+        matched[layer.point] = Some(node);
+        match &layer.routes[chosen[l]] {
+            Some((cost, path)) if !nodes.is_empty() => {
+                total += cost;
+                nodes.extend(path.iter().skip(1));
+            }
+            _ => nodes.push(node),
+        }
+    }
+
+    Ok(MatchedRoute {
+        distance: total,
+        nodes,
+        matched,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two parallel north-south roads 30m apart, 0..6 on the west and 6..12
+    // on the east, only joined at their ends
+    fn parallel_roads() -> (Graph, HashMap<usize, Coordinate>) {
+        let mut map = HashMap::new();
+        for i in 0..6 {
+            let lat = 4.60 + i as f64 * 0.0009;
+            map.insert(i, Coordinate { lat, lng: -74.08, id: i });
+            map.insert(i + 6, Coordinate { lat, lng: -74.07973, id: i + 6 });
+        }
+        let mut g = Graph::new(12);
+        let mut connect = |u: usize, v: usize| {
+            let w = map[&u].distance_to(&map[&v]);
+            g.add_edge(u, v, w);
+            g.add_edge(v, u, w);
+        };
+        for i in 0..5 {
+            connect(i, i + 1);
+            connect(i + 6, i + 7);
+        }
+        connect(0, 6);
+        connect(5, 11);
+        (g, map)
+    }
+
+    #[test]
+    fn test_match_stays_on_road() {
+        let (g, map) = parallel_roads();
+        // points 12m east of the west road, one of them drifting closer to the east road
+        let trace: Vec<Coordinate> = (0..6)
+            .map(|i| {
+                let drift = if i == 3 { 0.00017 } else { 0.00011 };
+                Coordinate { lat: map[&i].lat, lng: -74.08 + drift, id: 0 }
+            })
+            .collect();
+        let candidates: Vec<Vec<usize>> = (0..6).map(|i| vec![i, i + 6]).collect();
+        let mut workspace = SearchWorkspace::new(12);
+        let route = match_trace(
+            &mut workspace,
+            &g,
+            &map,
+            &trace,
+            &candidates,
+            &MatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(route.nodes, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(route.matched, (0..6).map(Some).collect::<Vec<_>>());
+        assert!((route.distance - map[&0].distance_to(&map[&5])).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_match_fills_gaps_and_skips_unmatched_points() {
+        let (g, map) = parallel_roads();
+        let trace = vec![map[&6], map[&7], map[&9], map[&11]];
+        let candidates = vec![vec![6, 0], vec![], vec![9, 3], vec![11, 5]];
+        let mut workspace = SearchWorkspace::new(12);
+        let route = match_trace(
+            &mut workspace,
+            &g,
+            &map,
+            &trace,
+            &candidates,
+            &MatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(route.nodes, vec![6, 7, 8, 9, 10, 11]);
+        assert_eq!(route.matched, vec![Some(6), None, Some(9), Some(11)]);
+    }
+
+    #[test]
+    fn test_match_breaks_on_unreachable_points() {
+        let (mut g, map) = parallel_roads();
+        // cut both roads between 2 and 3
+        g.edges[2].retain(|&v| v != 3);
+        g.edges[8].retain(|&v| v != 9);
+        g.weights = g.edges.iter().map(|e| e.iter().map(|_| 100.0).collect()).collect();
+        let trace = vec![map[&1], map[&2], map[&3], map[&4]];
+        let candidates: Vec<Vec<usize>> = (1..5).map(|i| vec![i]).collect();
+        let mut workspace = SearchWorkspace::new(12);
+        let route = match_trace(
+            &mut workspace,
+            &g,
+            &map,
+            &trace,
+            &candidates,
+            &MatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(route.nodes, vec![1, 2, 3, 4]);
+        assert_eq!(route.distance, 200.0);
+    }
+
+    #[test]
+    fn test_match_breaks_on_distant_points() {
+        // a road about 33km long: too far to search between its ends
+        let map: HashMap<usize, Coordinate> = (0..2)
+            .map(|i| (i, Coordinate { lat: 4.60 + i as f64 * 0.3, lng: -74.08, id: i }))
+            .collect();
+        let mut g = Graph::new(2);
+        g.add_edge(0, 1, map[&0].distance_to(&map[&1]));
+        let mut workspace = SearchWorkspace::new(2);
+        let route = match_trace(
+            &mut workspace,
+            &g,
+            &map,
+            &[map[&0], map[&1]],
+            &[vec![0], vec![1]],
+            &MatchOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(route.nodes, vec![0, 1]);
+        assert_eq!(route.distance, 0.0);
+    }
+
+    #[test]
+    fn test_match_without_candidates() {
+        let (g, map) = parallel_roads();
+        let mut workspace = SearchWorkspace::new(12);
+        let result = match_trace(
+            &mut workspace,
+            &g,
+            &map,
+            &[map[&0]],
+            &[vec![]],
+            &MatchOptions::default(),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -1,4 +1,19 @@
+pub mod avoid;
 pub mod bfs;
+pub mod contraction_hierarchies;
+pub mod edge_snap;
+pub mod error;
+pub mod elevation;
+pub mod hull;
+pub mod landmarks;
+pub mod maneuvers;
+pub mod map_matching;
+pub mod scc;
+pub mod search_workspace;
 pub mod shortest_paths;
+pub mod simplify;
+pub mod time_dependent;
+pub mod traffic;
 pub mod tsp_solver;
+pub mod turns;
 pub mod utils;
@@ -0,0 +1,185 @@
+use crate::ds::graph::Graph;
+use std::collections::HashSet;
+
+const UNVISITED: usize = usize::MAX;
+
+#[derive(Debug)]
+pub struct Components {
+    // component id of every node
+    pub component: Vec<usize>,
+    // number of nodes in every component
+    pub sizes: Vec<usize>,
+    // id of the component with the most nodes
+    pub largest: usize,
+    // components every component has an arc into, the condensation of the
+    // graph. Ids come in reverse topological order, so they are all lower.
+    pub successors: Vec<Vec<usize>>,
+}
+
+impl Components {
+    pub fn same(&self, u: usize, v: usize) -> bool {
+        self.component[u] == self.component[v]
+    }
+
+    pub fn in_largest(&self, v: usize) -> bool {
+        self.component[v] == self.largest
+    }
+
+    // whether there is a path from u to v
+    pub fn reaches(&self, u: usize, v: usize) -> bool {
+        self.component_reaches(self.component[u], self.component[v])
+    }
+
+    // whether component from leads to component to, found on the condensation
+    pub fn component_reaches(&self, from: usize, to: usize) -> bool {
+        let mut seen = HashSet::from([from]);
+        let mut stack = vec![from];
+        while let Some(c) = stack.pop() {
+            if c == to {
+                return true;
+            }
+            // components below to cannot lead back up to it
+            for &next in self.successors[c].iter() {
+                if next >= to && seen.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        false
+    }
+}
+
+// Tarjan's algorithm with an explicit call stack, road networks are far too
+// deep for recursion
+pub fn strongly_connected_components(g: &Graph) -> Components {
+    let n = g.edges.len();
+    let mut index = vec![UNVISITED; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut component = vec![UNVISITED; n];
+    let mut sizes = vec![];
+    let mut counter = 0;
+
+    for root in 0..n {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        // (node, next arc to look at)
+        let mut calls = vec![(root, 0)];
+        index[root] = counter;
+        low[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&mut (v, ref mut next)) = calls.last_mut() {
+            if let Some(&w) = g.edges[v].get(*next) {
+                *next += 1;
+                if index[w] == UNVISITED {
+                    index[w] = counter;
+                    low[w] = counter;
+                    counter += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low[parent] = low[parent].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let id = sizes.len();
+                let mut size = 0;
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    component[w] = id;
+                    size += 1;
+                    if w == v {
+                        break;
+                    }
+                }
+                sizes.push(size);
+            }
+        }
+    }
+
+    let largest = (0..sizes.len()).max_by_key(|&id| sizes[id]).unwrap_or(0);
+    let mut successors = vec![vec![]; sizes.len()];
+    for (u, neighbours) in g.edges.iter().enumerate() {
+        for &v in neighbours.iter() {
+            if component[u] != component[v] {
+                successors[component[u]].push(component[v]);
+            }
+        }
+    }
+    for next in successors.iter_mut() {
+        next.sort_unstable();
+        next.dedup();
+    }
+    Components {
+        component,
+        sizes,
+        largest,
+        successors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::shortest_paths::{dijkstra_all, INFINITY};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn test_strongly_connected_components() {
+        // cycle 0 -> 1 -> 2 -> 0 feeding the dead end 3 -> 4 -> 3, and 5 on its own
+        let mut g = Graph::new(6);
+        for (u, v) in [(0, 1), (1, 2), (2, 0), (2, 3), (3, 4), (4, 3)] {
+            g.add_edge(u, v, 1.0);
+        }
+        let components = strongly_connected_components(&g);
+        assert_eq!(components.sizes.len(), 3);
+        assert!(components.same(0, 2) && components.same(3, 4));
+        assert!(!components.same(2, 3) && !components.same(4, 5));
+        assert!(components.in_largest(1));
+        assert_eq!(components.sizes[components.component[3]], 2);
+        assert!(components.reaches(0, 4) && components.reaches(3, 4));
+        assert!(!components.reaches(4, 0) && !components.reaches(0, 5) && !components.reaches(5, 0));
+    }
+
+    #[test]
+    fn test_components_match_mutual_reachability() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut g = Graph::new(40);
+        for _ in 0..70 {
+            g.add_edge(rng.gen_range(0..40), rng.gen_range(0..40), 1.0);
+        }
+        let components = strongly_connected_components(&g);
+        let reach: Vec<Vec<f64>> = (0..40).map(|v| dijkstra_all(&g, v).0).collect();
+        for (u, from_u) in reach.iter().enumerate() {
+            for (v, from_v) in reach.iter().enumerate() {
+                let mutual = from_u[v] < INFINITY && from_v[u] < INFINITY;
+                assert_eq!(components.same(u, v), mutual);
+                assert_eq!(components.reaches(u, v), from_u[v] < INFINITY);
+            }
+        }
+        assert_eq!(components.sizes.iter().sum::<usize>(), 40);
+    }
+
+    #[test]
+    fn test_long_path_does_not_overflow() {
+        let n = 200_000;
+        let mut g = Graph::new(n);
+        for v in 0..n {
+            g.add_edge(v, (v + 1) % n, 1.0);
+        }
+        let components = strongly_connected_components(&g);
+        assert_eq!(components.sizes, vec![n]);
+    }
+}
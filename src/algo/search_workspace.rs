@@ -0,0 +1,228 @@
+use crate::algo::shortest_paths::{NodeInfo, INFINITY};
+use crate::ds::priority_queue::MinHeap;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+// Per-node state of one search direction. Entries are only valid when their
+// stamp matches the current generation, so starting a new query is O(1)
+// instead of O(V) and a query only touches the nodes it reaches.
+pub struct SearchSpace {
+    generation: u32,
+    reached: Vec<u32>,
+    settled: Vec<u32>,
+    dist: Vec<f64>,
+    prev: Vec<Option<usize>>,
+    heap: MinHeap<NodeInfo>,
+}
+
+impl SearchSpace {
+    pub fn new(n: usize) -> Self {
+        Self {
+            generation: 1,
+            reached: vec![0; n],
+            settled: vec![0; n],
+            dist: vec![INFINITY; n],
+            prev: vec![None; n],
+            heap: MinHeap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dist.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dist.is_empty()
+    }
+
+    pub fn reset(&mut self) {
+        self.heap.clear();
+        if self.generation == u32::MAX {
+            self.reached.iter_mut().for_each(|stamp| *stamp = 0);
+            self.settled.iter_mut().for_each(|stamp| *stamp = 0);
+            self.generation = 0;
+        }
+        self.generation += 1;
+    }
+
+    pub fn dist(&self, v: usize) -> f64 {
+        if self.reached[v] == self.generation {
+            self.dist[v]
+        } else {
+            INFINITY
+        }
+    }
+
+    pub fn prev(&self, v: usize) -> Option<usize> {
+        if self.reached[v] == self.generation {
+            self.prev[v]
+        } else {
+            None
+        }
+    }
+
+    pub fn is_settled(&self, v: usize) -> bool {
+        self.settled[v] == self.generation
+    }
+
+    pub fn settle(&mut self, v: usize) {
+        self.settled[v] = self.generation;
+    }
+
+    // records dist and prev for v and queues it with the given key
+    pub fn update(&mut self, v: usize, dist: f64, prev: Option<usize>, key: f64) {
+        self.reached[v] = self.generation;
+        self.dist[v] = dist;
+        self.prev[v] = prev;
+        if self.heap.contains(v) {
+            self.heap.change_priority(v, key);
+        } else {
+            self.heap.insert(NodeInfo { distance: key, id: v });
+        }
+    }
+
+    pub fn has_queued(&self) -> bool {
+        !self.heap.is_empty()
+    }
+
+    pub fn min_key(&self) -> f64 {
+        self.heap.get_min().distance
+    }
+
+    pub fn pop(&mut self) -> NodeInfo {
+        self.heap.extract_min()
+    }
+
+    // nodes from the root of the search to v, following prev
+    pub fn path_to(&self, v: usize) -> Vec<usize> {
+        let mut path = vec![v];
+        let mut current = v;
+        while let Some(node) = self.prev(current) {
+            path.push(node);
+            current = node;
+        }
+        path.reverse();
+        path
+    }
+}
+
+// state for one query: unidirectional searches only use the forward space
+pub struct SearchWorkspace {
+    pub forward: SearchSpace,
+    pub backward: SearchSpace,
+}
+
+impl SearchWorkspace {
+    pub fn new(n: usize) -> Self {
+        Self {
+            forward: SearchSpace::new(n),
+            backward: SearchSpace::new(n),
+        }
+    }
+
+    // grows the workspace for searches over a larger graph, such as the turn graph
+    pub fn fit(&mut self, n: usize) {
+        if self.forward.len() < n {
+            self.forward = SearchSpace::new(n);
+            self.backward = SearchSpace::new(n);
+        }
+    }
+}
+
+// Workspaces sized for one graph, handed out to Rocket workers. A worker keeps
+// its workspace for the duration of a request and gives it back on drop, so the
+// pool grows to the number of requests that search at the same time.
+pub struct WorkspacePool {
+    nodes: usize,
+    idle: Mutex<Vec<SearchWorkspace>>,
+}
+
+impl WorkspacePool {
+    pub fn new(nodes: usize) -> Self {
+        Self {
+            nodes,
+            idle: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn get(&self) -> PooledWorkspace<'_> {
+        let workspace = self.idle.lock().unwrap().pop();
+        PooledWorkspace {
+            pool: self,
+            workspace: Some(workspace.unwrap_or_else(|| SearchWorkspace::new(self.nodes))),
+        }
+    }
+}
+
+pub struct PooledWorkspace<'a> {
+    pool: &'a WorkspacePool,
+    workspace: Option<SearchWorkspace>,
+}
+
+impl Deref for PooledWorkspace<'_> {
+    type Target = SearchWorkspace;
+
+    fn deref(&self) -> &SearchWorkspace {
+        self.workspace.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledWorkspace<'_> {
+    fn deref_mut(&mut self) -> &mut SearchWorkspace {
+        self.workspace.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledWorkspace<'_> {
+    fn drop(&mut self) {
+        if let Some(workspace) = self.workspace.take() {
+            self.pool.idle.lock().unwrap().push(workspace);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_space_reset() {
+        let mut space = SearchSpace::new(3);
+        space.update(1, 4.0, Some(0), 4.0);
+        space.settle(1);
+        assert_eq!(space.dist(1), 4.0);
+        assert_eq!(space.path_to(1), vec![0, 1]);
+        space.reset();
+        assert_eq!(space.dist(1), INFINITY);
+        assert_eq!(space.prev(1), None);
+        assert!(!space.is_settled(1));
+        assert!(!space.has_queued());
+    }
+
+    #[test]
+    fn test_search_space_generation_wraps() {
+        let mut space = SearchSpace::new(2);
+        space.generation = u32::MAX;
+        space.update(0, 1.0, None, 1.0);
+        space.reset();
+        assert_eq!(space.dist(0), INFINITY);
+        space.update(1, 2.0, Some(0), 2.0);
+        assert_eq!(space.path_to(1), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_workspace_pool_reuses_workspaces() {
+        let pool = WorkspacePool::new(4);
+        {
+            let mut first = pool.get();
+            first.forward.update(2, 1.0, None, 1.0);
+            let second = pool.get();
+            assert_eq!(second.forward.len(), 4);
+        }
+        assert_eq!(pool.idle.lock().unwrap().len(), 2);
+        let _again = pool.get();
+        assert_eq!(pool.idle.lock().unwrap().len(), 1);
+    }
+}
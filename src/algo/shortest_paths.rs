@@ -1,15 +1,32 @@
 pub use crate::ds::graph::Graph;
 pub use crate::ds::priority_queue::MinHeap;
 use crate::{
+    algo::{
+        elevation::Elevations,
+        error::RoutingError,
+        search_workspace::{SearchSpace, SearchWorkspace},
+    },
     ds::priority_queue::Prioritiness,
     utils::
         coordinate::Coordinate,
 };
 use geoutils::Location;
-use std::{collections::HashMap, error::Error};
+use std::collections::{HashMap, HashSet};
 
 
-const INFINITY: f64 = 9999999.0;
+pub const INFINITY: f64 = 9999999.0;
+
+// estimate of the remaining cost between two nodes, looked up by node id
+pub type Heuristic<'a> = dyn Fn(&HashMap<usize, Coordinate>, usize, usize) -> f64 + 'a;
+
+// tells whether the arc u -> v may be used
+pub type EdgeFilter<'a> = dyn Fn(usize, usize) -> bool + 'a;
+
+// cost of the arc u -> v given its weight in the graph, None when it may not be used
+pub type EdgeCost<'a> = dyn Fn(usize, usize, f64) -> Option<f64> + 'a;
+
+// cost of a route and the nodes it goes through
+pub type Route = (f64, Vec<usize>);
 
 #[derive(Copy, Clone, Debug)]
 pub struct NodeInfo {
@@ -37,29 +54,49 @@ impl Prioritiness for NodeInfo {
     }
 }
 
+// one-off query with its own workspace; repeated queries should reuse one
+// through dijkstra_with_workspace
 pub fn dijkstra(
     g: &Graph,
     src: usize,
     dest: usize,
-) -> Result<(f64, Vec<Option<NodeInfo>>), Box<dyn Error>> {
-    let mut dist: Vec<f64> = vec![];
-    let mut prev = vec![];
-    let mut visited = vec![];
+) -> Result<(f64, Vec<Option<NodeInfo>>), RoutingError> {
+    let mut workspace = SearchWorkspace::new(g.edges.len());
+    let (cost, _) = dijkstra_with_workspace(&mut workspace, g, src, dest)?;
+    Ok((cost, predecessors(&workspace.forward)))
+}
+
+// one-off query with its own workspace; repeated queries should reuse one
+// through astar_with_workspace
+pub fn astar(
+    g: &Graph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+) -> Result<(f64, Vec<Option<NodeInfo>>), RoutingError> {
+    let mut workspace = SearchWorkspace::new(g.edges.len());
+    let (cost, _) = astar_with_workspace(&mut workspace, g, map, src, dest, heuristic)?;
+    Ok((cost, predecessors(&workspace.forward)))
+}
+
+// prev of every node reached by the last search, in the form reconstruct_path takes
+fn predecessors(space: &SearchSpace) -> Vec<Option<NodeInfo>> {
+    (0..space.len())
+        .map(|v| space.prev(v).map(|u| NodeInfo::new(space.dist(u), u)))
+        .collect()
+}
+
+// distances and shortest path tree from src to every node, INFINITY when unreachable
+pub fn dijkstra_all(g: &Graph, src: usize) -> (Vec<f64>, Vec<Option<usize>>) {
+    let mut dist = vec![INFINITY; g.edges.len()];
+    let mut prev = vec![None; g.edges.len()];
+    let mut visited = vec![false; g.edges.len()];
     let mut q = MinHeap::new();
-    for i in 0..g.edges.len() {
-        dist.push(INFINITY);
-        prev.push(None);
-        visited.push(false);
-        if i == src {
-            dist[i] = 0.0;
-        }
-        q.insert(NodeInfo::new(dist[i], i));
-    }
+    dist[src] = 0.0;
+    q.insert(NodeInfo::new(0.0, src));
     while !q.is_empty() {
         let node = q.extract_min();
-        if node.id == dest {
-            break;
-        }
         visited[node.id] = true;
         for (i, &neighbour) in g.edges[node.id].iter().enumerate() {
             if visited[neighbour] {
@@ -68,60 +105,345 @@ pub fn dijkstra(
             let alt = dist[node.id] + g.weights[node.id][i];
             if alt < dist[neighbour] {
                 dist[neighbour] = alt;
-                prev[neighbour] = Some(node);
-                q.change_priority(neighbour, alt);
+                prev[neighbour] = Some(node.id);
+                if q.contains(neighbour) {
+                    q.change_priority(neighbour, alt);
+                } else {
+                    q.insert(NodeInfo::new(alt, neighbour));
+                }
             }
         }
     }
-    if dist[dest] == INFINITY {
-        return Err("No path found".into());
+    (dist, prev)
+}
+
+// nodes reachable from src within max_cost and their costs, in the order they
+// are settled; every arc goes through edge_cost
+pub fn bounded_dijkstra(g: &Graph, src: usize, max_cost: f64, edge_cost: &EdgeCost<'_>) -> Vec<(usize, f64)> {
+    let mut dist: HashMap<usize, f64> = HashMap::new();
+    let mut reached = vec![];
+    let mut q = MinHeap::new();
+    dist.insert(src, 0.0);
+    q.insert(NodeInfo::new(0.0, src));
+    while !q.is_empty() {
+        let node = q.extract_min();
+        if node.distance > max_cost {
+            break;
+        }
+        reached.push((node.id, node.distance));
+        for (i, &neighbour) in g.edges[node.id].iter().enumerate() {
+            let Some(w) = edge_cost(node.id, neighbour, g.weights[node.id][i]) else {
+                continue;
+            };
+            let alt = node.distance + w;
+            if alt > max_cost || alt >= *dist.get(&neighbour).unwrap_or(&INFINITY) {
+                continue;
+            }
+            dist.insert(neighbour, alt);
+            if q.contains(neighbour) {
+                q.change_priority(neighbour, alt);
+            } else {
+                q.insert(NodeInfo::new(alt, neighbour));
+            }
+        }
     }
-    Ok((dist[dest], prev))
+    reached
 }
 
-pub fn astar(
+// same as dijkstra, but reusing the state kept in the workspace
+pub fn dijkstra_with_workspace(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    src: usize,
+    dest: usize,
+) -> Result<Route, RoutingError> {
+    search(&mut workspace.forward, g, src, dest, INFINITY, &|_| 0.0, &|_, _, w| Some(w))
+}
+
+// same as astar, but reusing the state kept in the workspace
+pub fn astar_with_workspace(
+    workspace: &mut SearchWorkspace,
     g: &Graph,
     map: &HashMap<usize, Coordinate>,
     src: usize,
     dest: usize,
-    heuristic: &dyn Fn(&HashMap<usize, Coordinate>, usize, usize) -> f64,
-) -> Result<(f64, Vec<Option<NodeInfo>>), Box<dyn Error>> {
-    let mut dist: Vec<f64> = vec![];
-    let mut prev = vec![];
-    let mut visited = vec![];
-    let mut q = MinHeap::new();
-    for i in 0..g.edges.len() {
-        dist.push(INFINITY);
-        prev.push(None);
-        visited.push(false);
-        if i == src {
-            dist[i] = 0.0;
+    heuristic: &Heuristic<'_>,
+) -> Result<Route, RoutingError> {
+    search(&mut workspace.forward, g, src, dest, INFINITY, &|v| heuristic(map, v, dest), &|_, _, w| Some(w))
+}
+
+// same as astar_with_workspace, but only over the arcs the filter lets through
+pub fn astar_with_filter(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+    filter: &EdgeFilter<'_>,
+) -> Result<Route, RoutingError> {
+    search(&mut workspace.forward, g, src, dest, INFINITY, &|v| heuristic(map, v, dest), &|u, v, w| filter(u, v).then_some(w))
+}
+
+// same as astar_with_workspace, but every arc goes through edge_cost, which
+// must not make it cheaper than its weight for the heuristic to stay admissible
+pub fn astar_with_edge_costs(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Route, RoutingError> {
+    search(&mut workspace.forward, g, src, dest, INFINITY, &|v| heuristic(map, v, dest), edge_cost)
+}
+
+// same as dijkstra_with_workspace, but every arc goes through edge_cost, which
+// may change its weight or drop it by returning None. Gives up with NoPath as
+// soon as dest is known to cost more than max_cost.
+pub fn dijkstra_with_edge_costs(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    src: usize,
+    dest: usize,
+    max_cost: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Route, RoutingError> {
+    search(&mut workspace.forward, g, src, dest, max_cost, &|_| 0.0, edge_cost)
+}
+
+// settles nodes by real distance plus potential until dest comes out of the
+// queue, or until the smallest key is above max_cost
+fn search(
+    space: &mut SearchSpace,
+    g: &Graph,
+    src: usize,
+    dest: usize,
+    max_cost: f64,
+    potential: &dyn Fn(usize) -> f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Route, RoutingError> {
+    space.reset();
+    space.update(src, 0.0, None, potential(src));
+    while space.has_queued() {
+        let node = space.pop();
+        if node.distance > max_cost {
+            break;
         }
-        q.insert(NodeInfo::new(dist[i], i));
-    }
-    while !q.is_empty() {
-        let node = q.extract_min();
         if node.id == dest {
+            return Ok((space.dist(dest), space.path_to(dest)));
+        }
+        space.settle(node.id);
+        for (i, &neighbour) in g.edges[node.id].iter().enumerate() {
+            if space.is_settled(neighbour) {
+                continue;
+            }
+            let Some(w) = edge_cost(node.id, neighbour, g.weights[node.id][i]) else {
+                continue;
+            };
+            let alt = space.dist(node.id) + w;
+            if alt < space.dist(neighbour) {
+                space.update(neighbour, alt, Some(node.id), alt + potential(neighbour));
+            }
+        }
+    }
+    Err(RoutingError::NoPath)
+}
+
+// edge cost adding penalty for every metre climbed along the arc; it only
+// makes arcs dearer, so it can be used with any admissible heuristic
+pub fn climb_penalty(elevations: &Elevations, penalty: f64) -> impl Fn(usize, usize, f64) -> Option<f64> + '_ {
+    move |u, v, w| Some(w + penalty * elevations.climb(u, v))
+}
+
+// costs from src to every target, None for the ones it cannot reach within
+// max_cost; the search stops as soon as every target is settled
+pub fn one_to_many(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    src: usize,
+    targets: &[usize],
+    max_cost: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Vec<Option<f64>> {
+    let space = &mut workspace.forward;
+    space.reset();
+    space.update(src, 0.0, None, 0.0);
+    let mut remaining: HashSet<usize> = targets.iter().copied().collect();
+    while space.has_queued() && !remaining.is_empty() {
+        let node = space.pop();
+        if node.distance > max_cost {
             break;
         }
-        visited[node.id] = true;
+        space.settle(node.id);
+        remaining.remove(&node.id);
         for (i, &neighbour) in g.edges[node.id].iter().enumerate() {
-            if visited[neighbour] {
+            if space.is_settled(neighbour) {
                 continue;
             }
-            let alt = dist[node.id] + g.weights[node.id][i] - heuristic(map, node.id, dest)
-                + heuristic(map, neighbour, dest);
-            if alt < dist[neighbour] {
-                dist[neighbour] = alt;
-                prev[neighbour] = Some(node);
-                q.change_priority(neighbour, alt);
+            let Some(w) = edge_cost(node.id, neighbour, g.weights[node.id][i]) else {
+                continue;
+            };
+            let alt = space.dist(node.id) + w;
+            if alt < space.dist(neighbour) {
+                space.update(neighbour, alt, Some(node.id), alt);
             }
         }
     }
-    if dist[dest] == INFINITY {
-        return Err("No path found".into());
+    targets
+        .iter()
+        .map(|&target| space.is_settled(target).then(|| space.dist(target)))
+        .collect()
+}
+
+// cheapest route from any of the sources to any of the targets, each given
+// with the cost of starting or finishing there. potential must not
+// overestimate the cost from a node to the closest target, and be 0 on them.
+// Gives up with NoPath once every route left would cost more than max_cost.
+pub fn multi_source_search(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    sources: &[(usize, f64)],
+    targets: &[(usize, f64)],
+    max_cost: f64,
+    potential: &dyn Fn(usize) -> f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Route, RoutingError> {
+    let space = &mut workspace.forward;
+    space.reset();
+    for &(src, cost) in sources {
+        if cost < space.dist(src) {
+            space.update(src, cost, None, cost + potential(src));
+        }
+    }
+    let mut best: Option<(f64, usize)> = None;
+    while space.has_queued() {
+        if best.is_some_and(|(cost, _)| space.min_key() >= cost) || space.min_key() > max_cost {
+            break;
+        }
+        let node = space.pop();
+        space.settle(node.id);
+        for &(target, cost) in targets.iter().filter(|&&(target, _)| target == node.id) {
+            let total = space.dist(target) + cost;
+            if best.is_none_or(|(best, _)| total < best) {
+                best = Some((total, target));
+            }
+        }
+        for (i, &neighbour) in g.edges[node.id].iter().enumerate() {
+            if space.is_settled(neighbour) {
+                continue;
+            }
+            let Some(w) = edge_cost(node.id, neighbour, g.weights[node.id][i]) else {
+                continue;
+            };
+            let alt = space.dist(node.id) + w;
+            if alt < space.dist(neighbour) {
+                space.update(neighbour, alt, Some(node.id), alt + potential(neighbour));
+            }
+        }
     }
-    Ok((dist[dest], prev))
+    let (cost, target) = best.filter(|&(cost, _)| cost <= max_cost).ok_or(RoutingError::NoPath)?;
+    Ok((cost, space.path_to(target)))
+}
+
+pub fn bidirectional_dijkstra(
+    g: &Graph,
+    reverse: &Graph,
+    src: usize,
+    dest: usize,
+) -> Result<Route, RoutingError> {
+    let mut workspace = SearchWorkspace::new(g.edges.len());
+    bidirectional_search(&mut workspace, g, reverse, src, dest, &|_| 0.0)
+}
+
+pub fn bidirectional_astar(
+    g: &Graph,
+    reverse: &Graph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+) -> Result<Route, RoutingError> {
+    let mut workspace = SearchWorkspace::new(g.edges.len());
+    bidirectional_astar_with_workspace(&mut workspace, g, reverse, map, src, dest, heuristic)
+}
+
+// bidirectional A* with average potentials: the forward search uses
+// (h(v, dest) - h(src, v)) / 2 and the backward search its negation,
+// so both sides work on the same reduced costs and can stop as soon as
+// their smallest keys add up to the best meeting cost.
+pub fn bidirectional_astar_with_workspace(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    reverse: &Graph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+) -> Result<Route, RoutingError> {
+    let potential = |v: usize| (heuristic(map, v, dest) - heuristic(map, src, v)) / 2.0;
+    bidirectional_search(workspace, g, reverse, src, dest, &potential)
+}
+
+fn bidirectional_search(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    reverse: &Graph,
+    src: usize,
+    dest: usize,
+    potential: &dyn Fn(usize) -> f64,
+) -> Result<Route, RoutingError> {
+    let SearchWorkspace { forward, backward } = workspace;
+    forward.reset();
+    backward.reset();
+    // the backward space keeps, for every node, the next node towards dest
+    forward.update(src, 0.0, None, potential(src));
+    backward.update(dest, 0.0, None, -potential(dest));
+
+    let mut best = if src == dest { 0.0 } else { INFINITY };
+    let mut meeting = src;
+
+    while forward.has_queued() && backward.has_queued() {
+        if forward.min_key() + backward.min_key() >= best {
+            break;
+        }
+
+        let is_forward = forward.min_key() <= backward.min_key();
+        let (space, other, arcs, sign) = if is_forward {
+            (&mut *forward, &*backward, g, 1.0)
+        } else {
+            (&mut *backward, &*forward, reverse, -1.0)
+        };
+
+        let node = space.pop();
+        space.settle(node.id);
+        for (i, &neighbour) in arcs.edges[node.id].iter().enumerate() {
+            if space.is_settled(neighbour) {
+                continue;
+            }
+            let alt = space.dist(node.id) + arcs.weights[node.id][i];
+            if alt < space.dist(neighbour) {
+                space.update(neighbour, alt, Some(node.id), alt + sign * potential(neighbour));
+                if alt + other.dist(neighbour) < best {
+                    best = alt + other.dist(neighbour);
+                    meeting = neighbour;
+                }
+            }
+        }
+    }
+
+    if best == INFINITY {
+        return Err(RoutingError::NoPath);
+    }
+
+    let mut path = forward.path_to(meeting);
+    let mut current = meeting;
+    while let Some(node) = backward.prev(current) {
+        path.push(node);
+        current = node;
+    }
+    Ok((best, path))
 }
 
 pub fn harvesine_heuristic(
@@ -139,7 +461,10 @@ pub fn harvesine_heuristic(
 pub fn reconstruct_path(
     prev: Vec<Option<NodeInfo>>,
     dest: usize,
-) -> Result<Vec<usize>, Box<dyn Error>> {
+) -> Result<Vec<usize>, RoutingError> {
+    if dest >= prev.len() {
+        return Err(RoutingError::NodeOutOfRange(dest));
+    }
     let mut path = vec![];
     path.push(dest);
     let mut current = dest;
@@ -151,9 +476,271 @@ pub fn reconstruct_path(
     Ok(path)
 }
 
+// cheapest arc u -> v, None when the nodes are not adjacent
+pub fn edge_weight(g: &Graph, u: usize, v: usize) -> Option<f64> {
+    g.edges[u]
+        .iter()
+        .zip(g.weights[u].iter())
+        .filter(|(&neighbour, _)| neighbour == v)
+        .map(|(_, &w)| w)
+        .reduce(f64::min)
+}
+
+pub fn path_cost(g: &Graph, path: &[usize]) -> f64 {
+    path.windows(2)
+        .map(|pair| edge_weight(g, pair[0], pair[1]).unwrap_or(INFINITY))
+        .sum()
+}
+
+// cost of path with every arc going through edge_cost
+fn path_cost_with_edge_costs(g: &Graph, path: &[usize], edge_cost: &EdgeCost<'_>) -> f64 {
+    path.windows(2)
+        .map(|pair| edge_weight(g, pair[0], pair[1]).and_then(|w| edge_cost(pair[0], pair[1], w)).unwrap_or(INFINITY))
+        .sum()
+}
+
+// Yen's algorithm: the k cheapest loopless paths, cheapest first
+pub fn k_shortest_paths(
+    g: &Graph,
+    src: usize,
+    dest: usize,
+    k: usize,
+) -> Result<Vec<Route>, RoutingError> {
+    let mut workspace = SearchWorkspace::new(g.edges.len());
+    let mut found = vec![dijkstra_with_workspace(&mut workspace, g, src, dest)?];
+    let mut candidates: Vec<Route> = vec![];
+
+    while found.len() < k {
+        let last = found[found.len() - 1].1.clone();
+        for j in 0..last.len() - 1 {
+            let spur = last[j];
+            let root = &last[..=j];
+
+            let mut blocked_edges = HashSet::new();
+            for (_, path) in found.iter() {
+                if path.len() > j + 1 && &path[..=j] == root {
+                    blocked_edges.insert((path[j], path[j + 1]));
+                }
+            }
+            let blocked_nodes: HashSet<usize> = root[..j].iter().copied().collect();
+            let edge_cost = |u: usize, v: usize, w: f64| {
+                if blocked_nodes.contains(&v) || blocked_edges.contains(&(u, v)) {
+                    None
+                } else {
+                    Some(w)
+                }
+            };
+
+            if let Ok((spur_cost, spur_path)) = dijkstra_with_edge_costs(&mut workspace, g, spur, dest, INFINITY, &edge_cost) {
+                let mut path = root.to_vec();
+                path.extend_from_slice(&spur_path[1..]);
+                let cost = path_cost(g, root) + spur_cost;
+                let known = found.iter().chain(candidates.iter()).any(|(_, p)| *p == path);
+                if !known {
+                    candidates.push((cost, path));
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            break;
+        }
+        let (best, _) = candidates
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1 .0.partial_cmp(&b.1 .0).unwrap())
+            .unwrap();
+        found.push(candidates.remove(best));
+    }
+    Ok(found)
+}
+
+pub struct AlternativeOptions {
+    pub max_alternatives: usize,
+    // alternatives may cost at most this factor times the shortest path
+    pub max_stretch: f64,
+    // share of an alternative's cost it may have in common with any accepted route
+    pub max_overlap: f64,
+    // subpaths of this share of the route cost must be shortest paths
+    pub local_optimality: f64,
+    // weight factor applied to the arcs of every route found so far
+    pub penalty: f64,
+}
+
+impl Default for AlternativeOptions {
+    fn default() -> Self {
+        Self {
+            max_alternatives: 3,
+            max_stretch: 1.4,
+            max_overlap: 0.7,
+            local_optimality: 0.25,
+            penalty: 1.4,
+        }
+    }
+}
+
+// share of the cost of `path` spent on arcs that `other` also uses
+pub fn overlap(g: &Graph, path: &[usize], other: &[usize]) -> f64 {
+    let total = path_cost(g, path);
+    if total == 0.0 {
+        return 1.0;
+    }
+    let other_edges: HashSet<(usize, usize)> = other.windows(2).map(|e| (e[0], e[1])).collect();
+    let shared: f64 = path
+        .windows(2)
+        .filter(|e| other_edges.contains(&(e[0], e[1])))
+        .map(|e| edge_weight(g, e[0], e[1]).unwrap_or(INFINITY))
+        .sum();
+    shared / total
+}
+
+// T-test: every window of the path costing about alpha times its total
+// cost has to be a shortest path itself, so the route has no silly detours.
+// Windows start every half window and overlap by at least one arc. Costs are
+// the weights as changed by edge_cost.
+pub fn is_locally_optimal(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    path: &[usize],
+    alpha: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> bool {
+    let mut cumulative = vec![0.0];
+    for e in path.windows(2) {
+        cumulative.push(cumulative[cumulative.len() - 1] + path_cost_with_edge_costs(g, e, edge_cost));
+    }
+    let window = alpha * cumulative[cumulative.len() - 1];
+    let mut start = 0;
+    while start < path.len() - 1 {
+        // a window spans at least two arcs, a single arc is trivially optimal
+        let mut end = (start + 2).min(path.len() - 1);
+        while end < path.len() - 1 && cumulative[end] - cumulative[start] < window {
+            end += 1;
+        }
+        let expected = cumulative[end] - cumulative[start];
+        // only a strictly shorter path matters, so the search stops at expected
+        let shorter = dijkstra_with_edge_costs(
+            workspace,
+            g,
+            path[start],
+            path[end],
+            expected - 1e-6,
+            edge_cost,
+        );
+        if shorter.is_ok() {
+            return false;
+        }
+        if end == path.len() - 1 {
+            break;
+        }
+        let next = start;
+        start += 1;
+        while start < end - 1 && cumulative[start] - cumulative[next] < window / 2.0 {
+            start += 1;
+        }
+    }
+    true
+}
+
+// keeps the candidates that pass the stretch, overlap and local optimality
+// filters, the first candidate is taken as the shortest path
+pub fn filter_alternatives(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    candidates: Vec<Route>,
+    options: &AlternativeOptions,
+    edge_cost: &EdgeCost<'_>,
+) -> Vec<Route> {
+    let mut accepted: Vec<Route> = vec![];
+    for (cost, path) in candidates {
+        if accepted.len() > options.max_alternatives {
+            break;
+        }
+        if let Some((best, _)) = accepted.first() {
+            let admissible = cost <= best * options.max_stretch
+                && accepted
+                    .iter()
+                    .all(|(_, other)| overlap(g, &path, other) <= options.max_overlap)
+                && is_locally_optimal(workspace, g, &path, options.local_optimality, edge_cost);
+            if !admissible {
+                continue;
+            }
+        }
+        accepted.push((cost, path));
+    }
+    accepted
+}
+
+// penalty method: search again and again with the arcs of the routes found
+// so far made more expensive, keeping the results that pass the filters.
+// The first route is the shortest path, followed by up to max_alternatives others.
+// Every arc goes through edge_cost before the penalties apply.
+pub fn alternative_routes(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    src: usize,
+    dest: usize,
+    options: &AlternativeOptions,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Vec<Route>, RoutingError> {
+    alternative_routes_between(workspace, g, &[(src, 0.0)], &[(dest, 0.0)], options, edge_cost)
+}
+
+// same as alternative_routes, but the routes may start at any of the sources
+// and end at any of the targets, each given with the cost of starting or
+// finishing there
+pub fn alternative_routes_between(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    sources: &[(usize, f64)],
+    targets: &[(usize, f64)],
+    options: &AlternativeOptions,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Vec<Route>, RoutingError> {
+    let end_cost = |ends: &[(usize, f64)], node: usize| {
+        ends.iter().filter(|&&(end, _)| end == node).map(|&(_, cost)| cost).fold(INFINITY, f64::min)
+    };
+    let shortest = multi_source_search(workspace, g, sources, targets, INFINITY, &|_| 0.0, edge_cost)?;
+    let max_cost = shortest.0 * options.max_stretch;
+    let mut penalties: HashMap<(usize, usize), f64> = HashMap::new();
+    let mut max_penalty: f64 = 1.0;
+    let mut last = shortest.1.clone();
+    let mut routes = vec![shortest];
+
+    for _ in 0..options.max_alternatives.saturating_mul(4) {
+        if routes.len() > options.max_alternatives {
+            break;
+        }
+        for e in last.windows(2) {
+            let penalty = penalties.entry((e[0], e[1])).or_insert(1.0);
+            *penalty *= options.penalty;
+            max_penalty = max_penalty.max(*penalty);
+        }
+        let penalised = |u: usize, v: usize, w: f64| edge_cost(u, v, w).map(|w| w * penalties.get(&(u, v)).unwrap_or(&1.0));
+        // a route over max_cost is dropped anyway, and penalties scale its
+        // cost by at most max_penalty
+        let Ok((_, path)) = multi_source_search(workspace, g, sources, targets, max_cost * max_penalty, &|_| 0.0, &penalised) else {
+            break;
+        };
+        let cost = end_cost(sources, path[0]) + path_cost_with_edge_costs(g, &path, edge_cost) + end_cost(targets, path[path.len() - 1]);
+        if cost > max_cost {
+            break;
+        }
+        last = path.clone();
+        if routes.iter().any(|(_, p)| *p == path) {
+            continue;
+        }
+        let mut candidates = routes.clone();
+        candidates.push((cost, path));
+        routes = filter_alternatives(workspace, g, candidates, options, edge_cost);
+    }
+    Ok(routes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::algo::elevation::parse_elevations;
     use dotenvy::dotenv;
     use rand::Rng;
     use std::{env, time::Instant};
@@ -201,6 +788,375 @@ mod tests {
         assert_eq!(path, vec![0, 1, 2]);
     }
 
+    fn grid_map() -> (Graph, HashMap<usize, Coordinate>) {
+        // 3x3 grid of points roughly 1km apart, arcs weighted a bit above
+        // the straight-line distance so the haversine heuristic stays admissible
+        let mut map = HashMap::new();
+        for row in 0..3 {
+            for col in 0..3 {
+                let id = row * 3 + col;
+                let coordinate = Coordinate {
+                    lat: 4.60 + row as f64 * 0.01,
+                    lng: -74.08 + col as f64 * 0.01,
+                    id,
+                };
+                map.insert(id, coordinate);
+            }
+        }
+        let mut g = Graph::new(9);
+        let mut connect = |u: usize, v: usize, factor: f64| {
+            let w = harvesine_heuristic(&map, u, v) * factor;
+            g.add_edge(u, v, w);
+            g.add_edge(v, u, w);
+        };
+        connect(0, 1, 1.1);
+        connect(1, 2, 1.3);
+        connect(3, 4, 1.2);
+        connect(4, 5, 1.1);
+        connect(6, 7, 1.4);
+        connect(7, 8, 1.2);
+        connect(0, 3, 1.2);
+        connect(3, 6, 1.1);
+        connect(1, 4, 1.5);
+        connect(4, 7, 1.1);
+        connect(2, 5, 1.2);
+        connect(5, 8, 1.3);
+        (g, map)
+    }
+
+    #[test]
+    fn test_astar_cost_matches_dijkstra() {
+        let (g, map) = grid_map();
+        let (cost, prev) = dijkstra(&g, 0, 8).unwrap();
+        let (astar_cost, astar_prev) = astar(&g, &map, 0, 8, &harvesine_heuristic).unwrap();
+        assert!((cost - astar_cost).abs() < 1e-6);
+        assert_eq!(
+            reconstruct_path(prev, 8).unwrap(),
+            reconstruct_path(astar_prev, 8).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dijkstra_all() {
+        let mut g = Graph::new(4);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(1, 2, 1.0);
+        g.add_edge(0, 2, 3.0);
+        let (dist, prev) = dijkstra_all(&g, 0);
+        assert_eq!(dist, vec![0.0, 1.0, 2.0, INFINITY]);
+        assert_eq!(prev, vec![None, Some(0), Some(1), None]);
+    }
+
+    #[test]
+    fn test_bounded_dijkstra() {
+        let mut g = Graph::new(5);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(1, 2, 2.0);
+        g.add_edge(0, 2, 4.0);
+        g.add_edge(2, 3, 1.0);
+        g.add_edge(3, 4, 5.0);
+        let weight = |_: usize, _: usize, w: f64| Some(w);
+        let reached = bounded_dijkstra(&g, 0, 4.0, &weight);
+        assert_eq!(reached, vec![(0, 0.0), (1, 1.0), (2, 3.0), (3, 4.0)]);
+        assert_eq!(bounded_dijkstra(&g, 4, 10.0, &weight), vec![(4, 0.0)]);
+        // a slow arc out of 1 leaves the direct one to 2
+        let slow = |u: usize, _: usize, w: f64| Some(if u == 1 { w * 3.0 } else { w });
+        assert_eq!(bounded_dijkstra(&g, 0, 4.0, &slow), vec![(0, 0.0), (1, 1.0), (2, 4.0)]);
+    }
+
+    #[test]
+    fn test_bidirectional_dijkstra() {
+        let mut g = Graph::new(5);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(0, 2, 2.0);
+        g.add_edge(1, 2, 1.5);
+        g.add_edge(1, 3, 3.0);
+        g.add_edge(2, 3, 1.0);
+        g.add_edge(2, 4, 2.5);
+        g.add_edge(3, 4, 1.0);
+        let reverse = g.reverse();
+        for src in 0..5 {
+            for dest in 0..5 {
+                let expected = dijkstra(&g, src, dest);
+                let result = bidirectional_dijkstra(&g, &reverse, src, dest);
+                match expected {
+                    Ok((cost, prev)) => {
+                        let (bi_cost, path) = result.unwrap();
+                        assert_eq!(bi_cost, cost);
+                        assert_eq!(path, reconstruct_path(prev, dest).unwrap());
+                    }
+                    Err(_) => assert!(result.is_err()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_astar() {
+        let (g, map) = grid_map();
+        let reverse = g.reverse();
+        for src in 0..9 {
+            for dest in 0..9 {
+                let (cost, prev) = dijkstra(&g, src, dest).unwrap();
+                let (bi_cost, path) =
+                    bidirectional_astar(&g, &reverse, &map, src, dest, &harvesine_heuristic)
+                        .unwrap();
+                assert!((bi_cost - cost).abs() < 1e-6);
+                assert_eq!(path, reconstruct_path(prev, dest).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_searches_with_workspace() {
+        let (g, map) = grid_map();
+        let reverse = g.reverse();
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        for src in 0..9 {
+            for dest in 0..9 {
+                let (cost, prev) = dijkstra(&g, src, dest).unwrap();
+                let path = reconstruct_path(prev, dest).unwrap();
+                let expected = (cost, path);
+
+                let result = dijkstra_with_workspace(&mut workspace, &g, src, dest).unwrap();
+                assert_eq!(result, expected);
+                let result = astar_with_workspace(
+                    &mut workspace,
+                    &g,
+                    &map,
+                    src,
+                    dest,
+                    &harvesine_heuristic,
+                )
+                .unwrap();
+                assert!((result.0 - expected.0).abs() < 1e-6);
+                assert_eq!(result.1, expected.1);
+                let result = bidirectional_astar_with_workspace(
+                    &mut workspace,
+                    &g,
+                    &reverse,
+                    &map,
+                    src,
+                    dest,
+                    &harvesine_heuristic,
+                )
+                .unwrap();
+                assert_eq!(result.1, expected.1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_workspace_after_failed_search() {
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(2, 1, 1.0);
+        let mut workspace = SearchWorkspace::new(3);
+        assert!(dijkstra_with_workspace(&mut workspace, &g, 0, 2).is_err());
+        assert_eq!(
+            dijkstra_with_workspace(&mut workspace, &g, 2, 1).unwrap(),
+            (1.0, vec![2, 1])
+        );
+    }
+
+    #[test]
+    fn test_astar_with_filter() {
+        let (g, map) = grid_map();
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        // close the middle node and the arc 0 -> 1
+        let filter = |u: usize, v: usize| v != 4 && (u, v) != (0, 1);
+        let (cost, path) =
+            astar_with_filter(&mut workspace, &g, &map, 0, 8, &harvesine_heuristic, &filter)
+                .unwrap();
+        assert_eq!(path, vec![0, 3, 6, 7, 8]);
+        assert!((cost - path_cost(&g, &path)).abs() < 1e-9);
+        let blocked = |_: usize, v: usize| v != 8;
+        assert!(
+            astar_with_filter(&mut workspace, &g, &map, 0, 8, &harvesine_heuristic, &blocked)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_astar_with_edge_costs() {
+        let (g, map) = grid_map();
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        // slowing down the left column must move the route off it
+        let left = [0, 3, 6];
+        let slow = |u: usize, v: usize, w: f64| {
+            Some(if left.contains(&u) && left.contains(&v) {
+                w * 5.0
+            } else {
+                w
+            })
+        };
+        let (slow_cost, slow_path) =
+            astar_with_edge_costs(&mut workspace, &g, &map, 0, 8, &harvesine_heuristic, &slow)
+                .unwrap();
+        let (expected_cost, expected_path) =
+            dijkstra_with_edge_costs(&mut workspace, &g, 0, 8, INFINITY, &slow).unwrap();
+        assert!((slow_cost - expected_cost).abs() < 1e-9);
+        assert_eq!(slow_path, expected_path);
+        assert!(!slow_path
+            .windows(2)
+            .any(|e| left.contains(&e[0]) && left.contains(&e[1])));
+    }
+
+    #[test]
+    fn test_one_to_many() {
+        let (mut g, _) = grid_map();
+        // node 9 can be left but never reached
+        g.edges.push(vec![0]);
+        g.weights.push(vec![1.0]);
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let targets = [8, 0, 9, 4, 8];
+        for src in 0..9 {
+            let costs = one_to_many(&mut workspace, &g, src, &targets, INFINITY, &|_, _, w| Some(w));
+            for (&target, cost) in targets.iter().zip(costs) {
+                match dijkstra(&g, src, target) {
+                    Ok((expected, _)) => assert!((cost.unwrap() - expected).abs() < 1e-9),
+                    Err(_) => assert_eq!(cost, None),
+                }
+            }
+        }
+        let costs = one_to_many(&mut workspace, &g, 9, &[9, 0], INFINITY, &|_, _, w| Some(w));
+        assert_eq!(costs, vec![Some(0.0), Some(1.0)]);
+        let costs = one_to_many(&mut workspace, &g, 0, &[0, 8], 1.0, &|_, _, w| Some(w));
+        assert_eq!(costs, vec![Some(0.0), None]);
+    }
+
+    #[test]
+    fn test_multi_source_search() {
+        let (g, map) = grid_map();
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let cost = |_: usize, _: usize, w: f64| Some(w);
+        let potential = |v: usize| harvesine_heuristic(&map, v, 8).min(harvesine_heuristic(&map, v, 6));
+        let route = multi_source_search(&mut workspace, &g, &[(0, 0.0), (2, 0.0)], &[(8, 0.0), (6, 0.0)], INFINITY, &potential, &cost).unwrap();
+        let expected = [(0, 6), (0, 8), (2, 6), (2, 8)]
+            .iter()
+            .map(|&(src, dest)| dijkstra(&g, src, dest).unwrap().0)
+            .fold(INFINITY, f64::min);
+        assert!((route.0 - expected).abs() < 1e-9);
+
+        // starting and finishing costs count towards the route
+        let direct = dijkstra_with_workspace(&mut workspace, &g, 0, 8).unwrap();
+        let route = multi_source_search(&mut workspace, &g, &[(0, 0.0), (2, 1e6)], &[(8, 5.0), (6, 1e6)], INFINITY, &potential, &cost).unwrap();
+        assert!((route.0 - direct.0 - 5.0).abs() < 1e-9);
+        assert_eq!(route.1, direct.1);
+        let closed = multi_source_search(&mut workspace, &g, &[(0, 0.0)], &[(8, 0.0)], INFINITY, &|_| 0.0, &|_, _, _| None);
+        assert!(closed.is_err());
+        let too_far = multi_source_search(&mut workspace, &g, &[(0, 0.0)], &[(8, 5.0)], direct.0 + 4.0, &potential, &cost);
+        assert!(too_far.is_err());
+    }
+
+    #[test]
+    fn test_climb_penalty() {
+        let (g, map) = grid_map();
+        // a hill over nodes 1 and 4
+        let elevations = parse_elevations("0 0\n1 100\n2 0\n3 0\n4 100\n5 0\n6 0\n7 0\n8 0\n").unwrap();
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let flat = astar_with_edge_costs(&mut workspace, &g, &map, 0, 2, &harvesine_heuristic, &|_, _, w| Some(w)).unwrap();
+        assert_eq!(flat.1, vec![0, 1, 2]);
+        let climb = climb_penalty(&elevations, 100.0);
+        let (cost, path) = astar_with_edge_costs(&mut workspace, &g, &map, 0, 2, &harvesine_heuristic, &climb).unwrap();
+        assert_eq!(path, vec![0, 3, 6, 7, 8, 5, 2]);
+        assert!((cost - path_cost(&g, &path)).abs() < 1e-9);
+        // going down is free
+        assert_eq!(climb(1, 2, 5.0), Some(5.0));
+        assert_eq!(climb(2, 1, 5.0), Some(10005.0));
+    }
+
+    #[test]
+    fn test_bidirectional_no_path() {
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(2, 1, 1.0);
+        let reverse = g.reverse();
+        assert!(bidirectional_dijkstra(&g, &reverse, 0, 2).is_err());
+    }
+
+    #[test]
+    fn test_k_shortest_paths() {
+        // directed version of the example graph used to illustrate Yen's algorithm
+        let mut g = Graph::new(6);
+        g.add_edge(0, 1, 3.0);
+        g.add_edge(0, 2, 2.0);
+        g.add_edge(1, 3, 4.0);
+        g.add_edge(2, 1, 1.0);
+        g.add_edge(2, 3, 2.0);
+        g.add_edge(2, 4, 3.0);
+        g.add_edge(3, 4, 2.0);
+        g.add_edge(3, 5, 1.0);
+        g.add_edge(4, 5, 2.0);
+        let paths = k_shortest_paths(&g, 0, 5, 3).unwrap();
+        let costs: Vec<f64> = paths.iter().map(|p| p.0).collect();
+        assert_eq!(costs, vec![5.0, 7.0, 8.0]);
+        assert_eq!(paths[0].1, vec![0, 2, 3, 5]);
+        assert_eq!(paths[1].1, vec![0, 2, 4, 5]);
+        for (cost, path) in paths {
+            assert_eq!(path_cost(&g, &path), cost);
+        }
+        assert_eq!(k_shortest_paths(&g, 0, 5, 10).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_overlap_and_local_optimality() {
+        let mut g = Graph::new(5);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(1, 2, 1.0);
+        g.add_edge(0, 3, 1.0);
+        g.add_edge(3, 2, 1.0);
+        g.add_edge(1, 4, 5.0);
+        g.add_edge(4, 2, 5.0);
+        assert_eq!(overlap(&g, &[0, 1, 2], &[0, 3, 2]), 0.0);
+        assert_eq!(overlap(&g, &[0, 1, 4, 2], &[0, 1, 2]), 1.0 / 11.0);
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let weight = |_: usize, _: usize, w: f64| Some(w);
+        assert!(is_locally_optimal(&mut workspace, &g, &[0, 3, 2], 0.25, &weight));
+        assert!(!is_locally_optimal(&mut workspace, &g, &[0, 1, 4, 2], 0.25, &weight));
+    }
+
+    #[test]
+    fn test_alternative_routes() {
+        // two disjoint corridors of similar cost and a long detour
+        let mut g = Graph::new(10);
+        for (u, v) in [(0, 1), (1, 2), (2, 3), (3, 9)] {
+            g.add_edge(u, v, 3.0);
+        }
+        for (u, v) in [(0, 4), (4, 5), (5, 6), (6, 9)] {
+            g.add_edge(u, v, 3.2);
+        }
+        for (u, v) in [(0, 7), (7, 8), (8, 9)] {
+            g.add_edge(u, v, 20.0);
+        }
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let weight = |_: usize, _: usize, w: f64| Some(w);
+        let routes =
+            alternative_routes(&mut workspace, &g, 0, 9, &AlternativeOptions::default(), &weight).unwrap();
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].1, vec![0, 1, 2, 3, 9]);
+        assert_eq!(routes[1].1, vec![0, 4, 5, 6, 9]);
+        assert!((routes[1].0 - 12.8).abs() < 1e-9);
+
+        let unbounded = AlternativeOptions {
+            max_alternatives: usize::MAX,
+            ..Default::default()
+        };
+        let routes = alternative_routes(&mut workspace, &g, 0, 9, &unbounded, &weight).unwrap();
+        assert_eq!(routes.len(), 2);
+        let too_far = dijkstra_with_edge_costs(&mut workspace, &g, 0, 9, 11.9, &|_, _, w| Some(w));
+        assert!(too_far.is_err());
+
+        // traffic on the first corridor makes the second one the shortest
+        let traffic = |u: usize, _: usize, w: f64| Some(if (1..=3).contains(&u) { w * 1.5 } else { w });
+        let routes =
+            alternative_routes(&mut workspace, &g, 0, 9, &AlternativeOptions::default(), &traffic).unwrap();
+        assert_eq!(routes[0].1, vec![0, 4, 5, 6, 9]);
+        assert!((routes[0].0 - 12.8).abs() < 1e-9);
+        assert_eq!(routes[1].1, vec![0, 1, 2, 3, 9]);
+        assert!((routes[1].0 - 16.5).abs() < 1e-9);
+    }
+
     #[test]
     #[ignore]
     fn test_dijstra_running_time() {
@@ -255,4 +1211,37 @@ mod tests {
         let _path = reconstruct_path(prev.1, dest).unwrap();
         println!("A* time: {:?}", start.elapsed());
     }
+
+    #[test]
+    #[ignore = "performance test"]
+    fn compare_bidirectional_running_times() {
+        dotenv().ok();
+        let coordinates_file = env::var("COORDINATES_FILE").unwrap();
+        let arcs_file = env::var("ARCS_FILE").unwrap();
+        let g = create_adjacency_list_from_files(&coordinates_file, &arcs_file).unwrap();
+        let reverse = g.reverse();
+        let map = create_id_to_coordinates_hashmap_from_file(&coordinates_file).unwrap();
+        let src = rand::thread_rng().gen_range(0..g.edges.len());
+        let dest = rand::thread_rng().gen_range(0..g.edges.len());
+
+        let start = Instant::now();
+        let prev = dijkstra(&g, src, dest).unwrap();
+        let path = reconstruct_path(prev.1, dest).unwrap();
+        println!("Dijkstra time: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let _prev = astar(&g, &map, src, dest, &harvesine_heuristic).unwrap();
+        println!("A* time: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let bi = bidirectional_dijkstra(&g, &reverse, src, dest).unwrap();
+        println!("Bidirectional Dijkstra time: {:?}", start.elapsed());
+        assert!((bi.0 - prev.0).abs() < 1e-6);
+
+        let start = Instant::now();
+        let bi_astar =
+            bidirectional_astar(&g, &reverse, &map, src, dest, &harvesine_heuristic).unwrap();
+        println!("Bidirectional A* time: {:?}", start.elapsed());
+        assert_eq!(bi_astar.1, path);
+    }
 }
@@ -0,0 +1,92 @@
+use crate::utils::coordinate::Coordinate;
+
+const EARTH_RADIUS: f64 = 6371000.0;
+
+// closest point to p on the segment a-b, as the fraction of the way from a to
+// b, and its distance in metres to p. Uses a local flat projection, which is
+// accurate enough at the scale of a road segment.
+pub fn project_onto_segment(p: &Coordinate, a: &Coordinate, b: &Coordinate) -> (f64, f64) {
+    let scale = EARTH_RADIUS * std::f64::consts::PI / 180.0;
+    let cos_lat = a.lat.to_radians().cos();
+    let project = |c: &Coordinate| ((c.lng - a.lng) * cos_lat * scale, (c.lat - a.lat) * scale);
+    let (px, py) = project(p);
+    let (bx, by) = project(b);
+    let length = bx * bx + by * by;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        ((px * bx + py * by) / length).clamp(0.0, 1.0)
+    };
+    (t, ((px - t * bx).powi(2) + (py - t * by).powi(2)).sqrt())
+}
+
+fn distance_to_segment(p: &Coordinate, a: &Coordinate, b: &Coordinate) -> f64 {
+    project_onto_segment(p, a, b).1
+}
+
+// Douglas-Peucker: indices of the points to keep so that no dropped point is
+// farther than tolerance metres from the simplified line. The first and last
+// points are always kept.
+pub fn douglas_peucker(points: &[Coordinate], tolerance: f64) -> Vec<usize> {
+    let n = points.len();
+    if n < 3 {
+        return (0..n).collect();
+    }
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[n - 1] = true;
+    let mut stack = vec![(0, n - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let mut farthest = None;
+        let mut max_distance = tolerance;
+        for (i, point) in points.iter().enumerate().take(last).skip(first + 1) {
+            let distance = distance_to_segment(point, &points[first], &points[last]);
+            if distance > max_distance {
+                max_distance = distance;
+                farthest = Some(i);
+            }
+        }
+        if let Some(i) = farthest {
+            keep[i] = true;
+            stack.push((first, i));
+            stack.push((i, last));
+        }
+    }
+    (0..n).filter(|&i| keep[i]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinate(lat: f64, lng: f64) -> Coordinate {
+        Coordinate { lat, lng, id: 0 }
+    }
+
+    #[test]
+    fn test_douglas_peucker() {
+        // straight line along a meridian with a 100 m bump in the middle
+        let mut points: Vec<Coordinate> = (0..11)
+            .map(|i| coordinate(4.60 + f64::from(i) * 0.001, -74.08))
+            .collect();
+        points[5].lng += 0.0009;
+        assert_eq!(douglas_peucker(&points, 90.0), vec![0, 5, 10]);
+        assert_eq!(douglas_peucker(&points, 50.0), vec![0, 4, 5, 6, 10]);
+        assert_eq!(douglas_peucker(&points, 500.0), vec![0, 10]);
+        assert_eq!(douglas_peucker(&points[..2], 50.0), vec![0, 1]);
+        assert!(douglas_peucker(&[], 50.0).is_empty());
+    }
+
+    #[test]
+    fn test_distance_to_segment() {
+        let a = coordinate(0.0, 0.0);
+        let b = coordinate(0.0, 0.01);
+        // about 111 m north of the middle of the segment
+        let d = distance_to_segment(&coordinate(0.001, 0.005), &a, &b);
+        assert!((d - 111.2).abs() < 0.5);
+        // past the end the distance is to the endpoint
+        let d = distance_to_segment(&coordinate(0.0, 0.011), &a, &b);
+        assert!((d - 111.2).abs() < 0.5);
+        assert_eq!(distance_to_segment(&a, &a, &a), 0.0);
+    }
+}
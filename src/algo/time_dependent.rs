@@ -0,0 +1,290 @@
+use crate::algo::error::RoutingError;
+use crate::algo::search_workspace::{SearchSpace, SearchWorkspace};
+use crate::algo::shortest_paths::{EdgeCost, Route, INFINITY};
+use crate::algo::turns::TurnGraph;
+use crate::ds::graph::Graph;
+use chrono::{NaiveTime, Timelike};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+pub const DAY: f64 = 86400.0;
+
+pub fn seconds_of_day(time: &NaiveTime) -> f64 {
+    f64::from(time.num_seconds_from_midnight())
+}
+
+// Travel time in seconds of an arc as a piecewise-linear function of the
+// departure time, repeating every day. Between the last and the first point
+// it interpolates across midnight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    points: Vec<(f64, f64)>,
+}
+
+impl Profile {
+    pub fn new(mut points: Vec<(f64, f64)>) -> Result<Self, Box<dyn Error>> {
+        if points.is_empty() {
+            return Err("Empty travel time profile".into());
+        }
+        if points.iter().any(|&(time, travel)| {
+            !(0.0..DAY).contains(&time) || !travel.is_finite() || travel < 0.0
+        }) {
+            return Err("Invalid travel time profile".into());
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let profile = Self { points };
+        // leaving later must never mean arriving earlier, otherwise Dijkstra
+        // on arrival times is no longer exact
+        for i in 0..profile.points.len() {
+            let (t0, travel0) = profile.points[i];
+            let (t1, travel1) = profile.next(i);
+            if t1 > t0 && travel1 - travel0 < -(t1 - t0) {
+                return Err("Travel time profile is not FIFO".into());
+            }
+        }
+        Ok(profile)
+    }
+
+    // the point after i, shifted a day ahead when it wraps around
+    fn next(&self, i: usize) -> (f64, f64) {
+        match self.points.get(i + 1) {
+            Some(&point) => point,
+            None => (self.points[0].0 + DAY, self.points[0].1),
+        }
+    }
+
+    pub fn travel_time(&self, departure: f64) -> f64 {
+        let mut time = departure.rem_euclid(DAY);
+        if time < self.points[0].0 {
+            time += DAY;
+        }
+        let i = self.points.partition_point(|&(t, _)| t <= time) - 1;
+        let (t0, travel0) = self.points[i];
+        let (t1, travel1) = self.next(i);
+        if t1 <= t0 {
+            return travel0;
+        }
+        travel0 + (travel1 - travel0) * (time - t0) / (t1 - t0)
+    }
+}
+
+// Arcs with a profile use it, the rest take their weight at free-flow speed
+#[derive(Debug)]
+pub struct TimeDependentGraph {
+    pub profiles: HashMap<(usize, usize), Profile>,
+    pub speed: f64,
+}
+
+impl TimeDependentGraph {
+    pub fn travel_time(&self, u: usize, v: usize, w: f64, departure: f64) -> f64 {
+        match self.profiles.get(&(u, v)) {
+            Some(profile) => profile.travel_time(departure),
+            None => w / self.speed,
+        }
+    }
+}
+
+// Dijkstra on arrival times: returns the travel time in seconds when leaving
+// src at departure, seconds since midnight, and the path taken. edge_cost
+// gets the travel time of every arc and may drop it by returning None.
+pub fn time_dependent_dijkstra(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    td: &TimeDependentGraph,
+    src: usize,
+    dest: usize,
+    departure: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Route, RoutingError> {
+    let space = &mut workspace.forward;
+    search(space, g, td, src, departure, edge_cost, &mut |node| node == dest);
+    if !space.is_settled(dest) {
+        return Err(RoutingError::NoPath);
+    }
+    Ok((space.dist(dest), space.path_to(dest)))
+}
+
+// travel times in seconds from src to every target when leaving at departure,
+// None for the ones it cannot reach; the search stops once every target is settled
+pub fn time_dependent_one_to_many(
+    workspace: &mut SearchWorkspace,
+    g: &Graph,
+    td: &TimeDependentGraph,
+    src: usize,
+    targets: &[usize],
+    departure: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Vec<Option<f64>> {
+    let space = &mut workspace.forward;
+    let mut remaining: HashSet<usize> = targets.iter().copied().collect();
+    search(space, g, td, src, departure, edge_cost, &mut |node| {
+        remaining.remove(&node);
+        remaining.is_empty()
+    });
+    targets
+        .iter()
+        .map(|&target| space.is_settled(target).then(|| space.dist(target)))
+        .collect()
+}
+
+// time_dependent_one_to_many over the turn graph, so forbidden turns are
+// never taken; turn penalties are distances and cost their free-flow time
+pub fn turn_aware_time_dependent_one_to_many(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    td: &TimeDependentGraph,
+    src: usize,
+    targets: &[usize],
+    departure: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Vec<Option<f64>> {
+    let arc_cost = |a: usize, elapsed: f64| {
+        let (u, v) = (turn_graph.arc_tail[a], turn_graph.arc_head[a]);
+        edge_cost(u, v, td.travel_time(u, v, turn_graph.arc_weight[a], departure + elapsed))
+    };
+    turn_graph.one_to_many(workspace, src, targets, INFINITY, &arc_cost, 1.0 / td.speed)
+}
+
+// settles nodes by arrival time until is_done returns true for one of them
+fn search(
+    space: &mut SearchSpace,
+    g: &Graph,
+    td: &TimeDependentGraph,
+    src: usize,
+    departure: f64,
+    edge_cost: &EdgeCost<'_>,
+    is_done: &mut dyn FnMut(usize) -> bool,
+) {
+    space.reset();
+    space.update(src, 0.0, None, 0.0);
+    while space.has_queued() {
+        let node = space.pop();
+        space.settle(node.id);
+        if is_done(node.id) {
+            return;
+        }
+        let elapsed = space.dist(node.id);
+        for (i, &neighbour) in g.edges[node.id].iter().enumerate() {
+            if space.is_settled(neighbour) {
+                continue;
+            }
+            let travel = td.travel_time(
+                node.id,
+                neighbour,
+                g.weights[node.id][i],
+                departure + elapsed,
+            );
+            let Some(travel) = edge_cost(node.id, neighbour, travel) else {
+                continue;
+            };
+            let alt = elapsed + travel;
+            if alt < space.dist(neighbour) {
+                space.update(neighbour, alt, Some(node.id), alt);
+            }
+        }
+    }
+}
+
+// profile lines are `from to HH:MM seconds HH:MM seconds ...`
+pub fn parse_profiles(content: &str) -> Result<HashMap<(usize, usize), Profile>, Box<dyn Error>> {
+    let mut profiles = HashMap::new();
+    for line in content.lines() {
+        let mut split_line = line.split_whitespace();
+        let Some(from) = split_line.next() else {
+            continue;
+        };
+        let from: usize = from.parse()?;
+        let to: usize = split_line.next().ok_or("Missing arc head")?.parse()?;
+        let mut points = vec![];
+        while let Some(time) = split_line.next() {
+            let time = NaiveTime::parse_from_str(time, "%H:%M")?;
+            let travel: f64 = split_line.next().ok_or("Missing travel time")?.parse()?;
+            points.push((seconds_of_day(&time), travel));
+        }
+        if profiles.insert((from, to), Profile::new(points)?).is_some() {
+            return Err(format!("Arc {} {} has more than one travel time profile", from, to).into());
+        }
+    }
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::turns::parse_turn_restrictions;
+
+    #[test]
+    fn test_profile_interpolation() {
+        let profile =
+            parse_profiles("0 1 07:00 100 08:00 400 10:00 100\n").unwrap()[&(0, 1)].clone();
+        assert_eq!(profile.travel_time(7.0 * 3600.0), 100.0);
+        assert_eq!(profile.travel_time(7.5 * 3600.0), 250.0);
+        assert_eq!(profile.travel_time(9.0 * 3600.0), 250.0);
+        assert_eq!(profile.travel_time(12.0 * 3600.0), 100.0);
+        assert_eq!(profile.travel_time(3.0 * 3600.0), 100.0);
+        assert_eq!(profile.travel_time(DAY + 7.5 * 3600.0), 250.0);
+
+        let constant = Profile::new(vec![(0.0, 60.0)]).unwrap();
+        assert_eq!(constant.travel_time(5000.0), 60.0);
+    }
+
+    #[test]
+    fn test_profile_validation() {
+        assert!(Profile::new(vec![]).is_err());
+        assert!(Profile::new(vec![(-1.0, 10.0)]).is_err());
+        assert!(Profile::new(vec![(0.0, -10.0)]).is_err());
+        // leaving at 01:00:30 would arrive before leaving at 01:00
+        assert!(Profile::new(vec![(3600.0, 100.0), (3630.0, 10.0)]).is_err());
+        assert!(parse_profiles("0 1 25:00 10\n").is_err());
+        assert!(parse_profiles("0 1 07:00\n").is_err());
+        assert!(Profile::new(vec![(0.0, f64::NAN)]).is_err());
+        assert!(Profile::new(vec![(f64::NAN, 10.0), (0.0, 10.0)]).is_err());
+        assert!(Profile::new(vec![(0.0, f64::INFINITY)]).is_err());
+        assert!(parse_profiles("0 1 07:00 10\n2 3 07:00 10\n0 1 08:00 20\n").is_err());
+    }
+
+    #[test]
+    fn test_time_dependent_dijkstra() {
+        // the direct arc 0 -> 2 is jammed in the morning
+        let mut g = Graph::new(3);
+        g.add_edge(0, 2, 100.0);
+        g.add_edge(0, 1, 100.0);
+        g.add_edge(1, 2, 100.0);
+        let td = TimeDependentGraph {
+            profiles: parse_profiles("0 2 06:00 10 07:00 100 09:00 100 10:00 10\n").unwrap(),
+            speed: 10.0,
+        };
+        let mut workspace = SearchWorkspace::new(3);
+        let all = |_: usize, _: usize, travel: f64| Some(travel);
+        let night = time_dependent_dijkstra(&mut workspace, &g, &td, 0, 2, 0.0, &all).unwrap();
+        assert_eq!(night, (10.0, vec![0, 2]));
+        let rush_hour =
+            time_dependent_dijkstra(&mut workspace, &g, &td, 0, 2, 8.0 * 3600.0, &all).unwrap();
+        assert_eq!(rush_hour, (20.0, vec![0, 1, 2]));
+        assert!(time_dependent_dijkstra(&mut workspace, &g, &td, 2, 0, 0.0, &all).is_err());
+        // closing the arc 0 -> 2 leaves the detour even at night
+        let closed = |u: usize, v: usize, travel: f64| ((u, v) != (0, 2)).then_some(travel);
+        let night = time_dependent_dijkstra(&mut workspace, &g, &td, 0, 2, 0.0, &closed).unwrap();
+        assert_eq!(night, (20.0, vec![0, 1, 2]));
+
+        let times =
+            time_dependent_one_to_many(&mut workspace, &g, &td, 0, &[2, 1, 0], 8.0 * 3600.0, &all);
+        assert_eq!(times, vec![Some(20.0), Some(10.0), Some(0.0)]);
+        let times = time_dependent_one_to_many(&mut workspace, &g, &td, 1, &[0, 2], 0.0, &all);
+        assert_eq!(times, vec![None, Some(10.0)]);
+
+        // a forbidden turn at 1 leaves only the jammed arc, a penalty of 50
+        // metres costs 5 seconds at free-flow speed
+        let mut restrictions = parse_turn_restrictions("0 1 2\n", None).unwrap();
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let times = turn_aware_time_dependent_one_to_many(&mut workspace, &turn_graph, &td, 0, &[2, 1], 8.0 * 3600.0, &all);
+        assert_eq!(times, vec![Some(100.0), Some(10.0)]);
+        restrictions.forbidden.clear();
+        restrictions.penalties.insert((0, 1, 2), 50.0);
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let times = turn_aware_time_dependent_one_to_many(&mut workspace, &turn_graph, &td, 0, &[2, 1], 8.0 * 3600.0, &all);
+        assert_eq!(times, vec![Some(25.0), Some(10.0)]);
+    }
+}
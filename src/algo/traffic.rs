@@ -0,0 +1,114 @@
+use crate::ds::graph::Graph;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error};
+
+// An arc is given either by its id, counting the arcs of each node in order,
+// or by the node pair it joins.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Segment {
+    Edge { edge: usize },
+    Nodes { from: usize, to: usize },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TrafficUpdate {
+    #[serde(flatten)]
+    pub segment: Segment,
+    pub multiplier: f64,
+}
+
+// Multipliers applied on top of the arc weights of the graph. Multipliers
+// below 1 are rejected, so the A* heuristics stay admissible.
+#[derive(Debug, Default)]
+pub struct WeightOverlay {
+    multipliers: HashMap<(usize, usize), f64>,
+}
+
+impl WeightOverlay {
+    pub fn new(g: &Graph, updates: &[TrafficUpdate]) -> Result<Self, Box<dyn Error>> {
+        let mut first_arc = vec![0];
+        for neighbours in g.edges.iter() {
+            first_arc.push(first_arc[first_arc.len() - 1] + neighbours.len());
+        }
+
+        let mut multipliers = HashMap::new();
+        for update in updates {
+            if update.multiplier.is_nan() || update.multiplier < 1.0 {
+                return Err(format!("Invalid multiplier {}", update.multiplier).into());
+            }
+            let arc = match update.segment {
+                Segment::Edge { edge } => {
+                    if edge >= first_arc[first_arc.len() - 1] {
+                        return Err(format!("Unknown edge {}", edge).into());
+                    }
+                    let u = first_arc.partition_point(|&first| first <= edge) - 1;
+                    (u, g.edges[u][edge - first_arc[u]])
+                }
+                Segment::Nodes { from, to } => {
+                    if from >= g.edges.len() || !g.edges[from].contains(&to) {
+                        return Err(format!("Unknown edge {} -> {}", from, to).into());
+                    }
+                    (from, to)
+                }
+            };
+            multipliers.insert(arc, update.multiplier);
+        }
+        Ok(Self { multipliers })
+    }
+
+    pub fn len(&self) -> usize {
+        self.multipliers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.multipliers.is_empty()
+    }
+
+    pub fn weight(&self, u: usize, v: usize, w: f64) -> f64 {
+        w * self.multipliers.get(&(u, v)).unwrap_or(&1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line() -> Graph {
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 2.0);
+        g.add_edge(0, 2, 5.0);
+        g.add_edge(1, 2, 2.0);
+        g.add_edge(2, 1, 2.0);
+        g
+    }
+
+    #[test]
+    fn test_weight_overlay() {
+        let updates: Vec<TrafficUpdate> = serde_json::from_str(
+            r#"[{"edge": 1, "multiplier": 2.0}, {"from": 2, "to": 1, "multiplier": 1.5}]"#,
+        )
+        .unwrap();
+        let overlay = WeightOverlay::new(&line(), &updates).unwrap();
+        assert_eq!(overlay.len(), 2);
+        assert_eq!(overlay.weight(0, 2, 5.0), 10.0);
+        assert_eq!(overlay.weight(2, 1, 2.0), 3.0);
+        assert_eq!(overlay.weight(1, 2, 2.0), 2.0);
+        assert!(WeightOverlay::default().is_empty());
+    }
+
+    #[test]
+    fn test_weight_overlay_rejects_invalid_updates() {
+        let update = |segment, multiplier| TrafficUpdate {
+            segment,
+            multiplier,
+        };
+        let g = line();
+        assert!(WeightOverlay::new(&g, &[update(Segment::Edge { edge: 4 }, 2.0)]).is_err());
+        assert!(WeightOverlay::new(&g, &[update(Segment::Nodes { from: 1, to: 0 }, 2.0)]).is_err());
+        assert!(WeightOverlay::new(&g, &[update(Segment::Nodes { from: 5, to: 0 }, 2.0)]).is_err());
+        assert!(WeightOverlay::new(&g, &[update(Segment::Edge { edge: 3 }, 0.5)]).is_err());
+        assert!(WeightOverlay::new(&g, &[update(Segment::Edge { edge: 3 }, f64::NAN)]).is_err());
+        assert!(WeightOverlay::new(&g, &[update(Segment::Edge { edge: 3 }, 1.0)]).is_ok());
+    }
+}
@@ -1,8 +1,21 @@
-use crate::algo::shortest_paths::{astar, harvesine_heuristic, reconstruct_path};
+use crate::algo::error::RoutingError;
+use crate::algo::search_workspace::SearchWorkspace;
+use crate::algo::shortest_paths::{astar_with_workspace, harvesine_heuristic};
 use crate::ds::graph::Graph;
 use crate::utils::coordinate::Coordinate;
 use geoutils::Location;
-use std::{collections::HashMap, error::Error};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+// seconds between the departure times the legs are searched at
+const DEPARTURE_BUCKET: f64 = 900.0;
+
+// Held-Karp takes O(2^n n^2) time and memory, so tours are kept small and
+// the search gives up after SOLVER_TIME_LIMIT
+pub const MAX_STOPS: usize = 16;
+const SOLVER_TIME_LIMIT: Duration = Duration::from_secs(30);
 
 pub struct TspSolver<'a> {
     pub road_network: &'a Graph,
@@ -30,9 +43,70 @@ impl<'a> TspSolver<'a> {
     }
 
     // it is assume that the first node is the starting node
-    pub fn held_karp_solve(&mut self) -> Result<Vec<usize>, Box<dyn Error>> {
+    pub fn held_karp_solve(&mut self) -> Result<Vec<usize>, RoutingError> {
         let dists = self.get_distance_matrix();
-        let n = dists.len();
+        self.held_karp_solve_with_costs(&dists)
+    }
+
+    // Same as held_karp_solve, but costs[i][j] gives the cost of going from
+    // node i to node j instead of the straight-line distance
+    pub fn held_karp_solve_with_costs(&mut self, costs: &[Vec<f64>]) -> Result<Vec<usize>, RoutingError> {
+        self.held_karp(&mut |m, k, _| costs[m][k])
+    }
+
+    // Same as held_karp_solve, but leg_costs(i, departure) gives the travel
+    // times from node i to every node when leaving at departure, in seconds
+    // since midnight. It only runs at multiples of DEPARTURE_BUCKET, once per
+    // node and time, and a leg leaving in between interpolates the two: the
+    // travel time profiles are piecewise linear, so this is exact unless a
+    // profile changes slope within the bucket.
+    pub fn held_karp_solve_time_dependent(
+        &mut self,
+        start: f64,
+        leg_costs: &mut dyn FnMut(usize, f64) -> Vec<f64>,
+    ) -> Result<Vec<usize>, RoutingError> {
+        let mut cache = HashMap::<(usize, i64), Vec<f64>>::new();
+        self.held_karp(&mut |m, k, elapsed| {
+            let departure = start + elapsed;
+            let bucket = (departure / DEPARTURE_BUCKET).floor();
+            let mut cost_at = |edge: f64| {
+                cache
+                    .entry((m, edge as i64))
+                    .or_insert_with(|| leg_costs(m, edge * DEPARTURE_BUCKET))[k]
+            };
+            let before = cost_at(bucket);
+            let share = departure / DEPARTURE_BUCKET - bucket;
+            if share == 0.0 {
+                return before;
+            }
+            before + (cost_at(bucket + 1.0) - before) * share
+        })
+    }
+
+    // lets callers reject a tour before computing its leg costs
+    pub fn check_stops(&self) -> Result<(), RoutingError> {
+        let n = self.nodes.len();
+        if n > MAX_STOPS {
+            return Err(RoutingError::TooManyStops { stops: n, max: MAX_STOPS });
+        }
+        Ok(())
+    }
+
+    // leg_cost(m, k, elapsed) is the cost of going from m to k after spending
+    // elapsed on the tour so far
+    fn held_karp(
+        &mut self,
+        leg_cost: &mut dyn FnMut(usize, usize, f64) -> f64,
+    ) -> Result<Vec<usize>, RoutingError> {
+        self.check_stops()?;
+        let n = self.nodes.len();
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        let started = Instant::now();
+        for i in 0..n {
+            self.new_nodes_to_original_nodes.insert(i, self.nodes[i].id);
+        }
 
         // Maps each subset of the nodes to the cost to reach that subset, as well
         // as what node it passed before reaching this subset.
@@ -41,13 +115,16 @@ impl<'a> TspSolver<'a> {
 
         // Set transition cost from initial state
         for k in 1..n {
-            c.insert((1 << k, k), (dists[0][k], 0));
+            c.insert((1 << k, k), (leg_cost(0, k, 0.0), 0));
         }
 
         // Iterate subsets of increasing size
         for subset_size in 2..n {
             // Iterate over subsets of this size
             for subset in (0..1 << n).filter(|s| i32::count_ones(*s) == subset_size as u32) {
+                if started.elapsed() > SOLVER_TIME_LIMIT {
+                    return Err(RoutingError::SolverTimeout);
+                }
                 // Iterate over last nodes in the subset
                 for k in 1..n {
                     // Skip if k is not in the subset
@@ -65,7 +142,8 @@ impl<'a> TspSolver<'a> {
                         }
 
                         let subset_without_k = subset ^ (1 << k);
-                        let cost = c.get(&(subset_without_k, m)).unwrap().0 + dists[m][k];
+                        let elapsed = c.get(&(subset_without_k, m)).unwrap().0;
+                        let cost = elapsed + leg_cost(m, k, elapsed);
                         if cost < min_prev {
                             min_prev = cost;
                             argmin_prev = m;
@@ -84,12 +162,14 @@ impl<'a> TspSolver<'a> {
         let mut min_cost = f64::INFINITY;
         let mut parent = 0;
         for k in 1..n {
-            let cost = c.get(&(subset, k)).unwrap().0 + dists[k][0];
+            let elapsed = c.get(&(subset, k)).unwrap().0;
+            let cost = elapsed + leg_cost(k, 0, elapsed);
             if cost < min_cost {
                 min_cost = cost;
                 parent = k;
             }
         }
+        self.distance = min_cost;
 
         self.path.push(0);
 
@@ -120,7 +200,6 @@ impl<'a> TspSolver<'a> {
     fn get_distance_matrix(&mut self) -> Vec<Vec<f64>> {
         let mut distance_matrix = vec![vec![0.0; self.nodes.len()]; self.nodes.len()];
         for i in 0..self.nodes.len() {
-            self.new_nodes_to_original_nodes.insert(i, self.nodes[i].id);
             for j in 0..self.nodes.len() {
                 if i == j {
                     distance_matrix[i][j] = 0.0;
@@ -137,20 +216,20 @@ impl<'a> TspSolver<'a> {
         distance_matrix
     }
 
-    fn _expand_path(&mut self) -> Result<Vec<usize>, Box<dyn Error>> {
+    fn _expand_path(&mut self) -> Result<Vec<usize>, RoutingError> {
         let mut new_path = vec![];
+        let mut workspace = SearchWorkspace::new(self.road_network.edges.len());
         for i in 0..self.path.len() - 1 {
             let start = self.path[i];
             let end = self.path[i + 1];
-            let path = astar(
+            let (_, path) = astar_with_workspace(
+                &mut workspace,
                 self.road_network,
                 self.id_to_coordinates,
                 start,
                 end,
                 &harvesine_heuristic,
-            )
-            .unwrap();
-            let path = reconstruct_path(path.1, end).unwrap();
+            )?;
             new_path.extend(path);
         }
         return Ok(new_path);
@@ -159,6 +238,8 @@ impl<'a> TspSolver<'a> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::algo::shortest_paths::INFINITY;
     use crate::utils::{
         create_adjacency_list_from_files, create_id_to_coordinates_hashmap_from_file,
     };
@@ -181,4 +262,96 @@ mod tests {
         // );
         // let path = tsp_solver.held_karp_solve().unwrap();
     }
+
+    #[test]
+    fn test_held_karp_time_dependent() {
+        let graph = Graph::new(0);
+        let map = HashMap::new();
+        let nodes: Vec<Coordinate> = (0..4)
+            .map(|id| Coordinate {
+                lat: 0.0,
+                lng: 0.0,
+                id: 10 + id,
+            })
+            .collect();
+        // going from the first node straight to the second is jammed from 08:00 to 09:00
+        let mut leg_costs = |m: usize, departure: f64| {
+            (0..4)
+                .map(|k| match (m, k) {
+                    (0, 1) if (8.0 * 3600.0..9.0 * 3600.0).contains(&departure) => 1000.0,
+                    (0, 1) => 10.0,
+                    _ => 100.0,
+                })
+                .collect()
+        };
+
+        let mut tsp = TspSolver::new(&graph, &map, nodes.clone());
+        let night = tsp.held_karp_solve_time_dependent(0.0, &mut leg_costs).unwrap();
+        assert_eq!((night[0], night[1], night[4]), (10, 11, 10));
+
+        let mut tsp = TspSolver::new(&graph, &map, nodes);
+        let rush_hour = tsp
+            .held_karp_solve_time_dependent(8.0 * 3600.0, &mut leg_costs)
+            .unwrap();
+        assert_eq!((rush_hour[0], rush_hour[4]), (10, 10));
+        assert_ne!(rush_hour[1], 11);
+    }
+
+    #[test]
+    fn test_time_dependent_legs_leave_at_their_departure() {
+        let graph = Graph::new(0);
+        let map = HashMap::new();
+        let nodes: Vec<Coordinate> = (0..3).map(|id| Coordinate { lat: 0.0, lng: 0.0, id }).collect();
+        // every leg gets slower through the day, and is only searched at
+        // the edges of the buckets
+        let travel = |departure: f64| 100.0 + departure / 100.0;
+        let mut departures = vec![];
+        let mut leg_costs = |_: usize, departure: f64| {
+            departures.push(departure);
+            vec![travel(departure); 3]
+        };
+        // the tour starts in the middle of a bucket and crosses into the next
+        let start = 850.0;
+        let mut tsp = TspSolver::new(&graph, &map, nodes);
+        tsp.held_karp_solve_time_dependent(start, &mut leg_costs).unwrap();
+        let mut clock = start;
+        for _ in 0..3 {
+            clock += travel(clock);
+        }
+        assert!((tsp.distance - (clock - start)).abs() < 1e-9);
+        assert!(departures.iter().all(|departure| departure % DEPARTURE_BUCKET == 0.0));
+    }
+
+    #[test]
+    fn test_held_karp_with_costs() {
+        let graph = Graph::new(0);
+        let map = HashMap::new();
+        // every stop is at the same place, only the road costs tell them apart
+        let nodes: Vec<Coordinate> = (0..4).map(|id| Coordinate { lat: 0.0, lng: 0.0, id: 10 + id }).collect();
+        // one-way streets: the cheap tour is 0 -> 2 -> 1 -> 3 -> 0, and 1
+        // cannot be reached from 0 at all
+        let costs = vec![
+            vec![0.0, INFINITY, 1.0, 50.0],
+            vec![50.0, 0.0, 50.0, 1.0],
+            vec![50.0, 1.0, 0.0, 50.0],
+            vec![1.0, 50.0, 50.0, 0.0],
+        ];
+        let mut tsp = TspSolver::new(&graph, &map, nodes);
+        assert_eq!(tsp.held_karp_solve_with_costs(&costs), Ok(vec![10, 12, 11, 13, 10]));
+        assert_eq!(tsp.distance, 4.0);
+    }
+
+    #[test]
+    fn test_too_many_stops() {
+        let graph = Graph::new(0);
+        let map = HashMap::new();
+        let nodes = vec![Coordinate { lat: 0.0, lng: 0.0, id: 0 }; MAX_STOPS + 1];
+        let mut tsp = TspSolver::new(&graph, &map, nodes);
+        assert_eq!(
+            tsp.held_karp_solve(),
+            Err(RoutingError::TooManyStops { stops: MAX_STOPS + 1, max: MAX_STOPS })
+        );
+        let mut tsp = TspSolver::new(&graph, &map, vec![]);
+        assert_eq!(tsp.held_karp_solve(), Ok(vec![]));
+    }
 }
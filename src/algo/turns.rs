@@ -0,0 +1,578 @@
+use crate::algo::error::RoutingError;
+use crate::algo::search_workspace::SearchWorkspace;
+use crate::algo::shortest_paths::{EdgeCost, EdgeFilter, Heuristic, Route, INFINITY};
+use crate::ds::graph::Graph;
+use crate::utils::coordinate::Coordinate;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+// used when no U-turn penalty is configured: forbidding U-turns outright
+// traps every route that runs into a dead end
+pub const DEFAULT_U_TURN_PENALTY: f64 = 100.0;
+
+// A turn goes from the arc `from -> via` onto the arc `via -> to`.
+#[derive(Debug, Default)]
+pub struct TurnRestrictions {
+    pub forbidden: HashSet<(usize, usize, usize)>,
+    pub penalties: HashMap<(usize, usize, usize), f64>,
+    // extra cost of turning back onto the node we came from, None forbids U-turns
+    pub u_turn_penalty: Option<f64>,
+}
+
+impl TurnRestrictions {
+    // cost added to the turn, None when it is not allowed
+    pub fn turn_cost(&self, from: usize, via: usize, to: usize) -> Option<f64> {
+        if self.forbidden.contains(&(from, via, to)) {
+            return None;
+        }
+        let mut cost = *self.penalties.get(&(from, via, to)).unwrap_or(&0.0);
+        if from == to {
+            cost += self.u_turn_penalty?;
+        }
+        Some(cost)
+    }
+}
+
+// Edge-expanded graph: every arc of the road graph becomes a node and every
+// allowed turn an arc, weighted with the cost of the arc it turns onto plus
+// the turn penalty.
+#[derive(Debug)]
+pub struct TurnGraph {
+    pub graph: Graph,
+    pub arc_tail: Vec<usize>,
+    pub arc_head: Vec<usize>,
+    pub arc_weight: Vec<f64>,
+    // arcs leaving node u are first_arc[u]..first_arc[u + 1]
+    pub first_arc: Vec<usize>,
+}
+
+impl TurnGraph {
+    pub fn new(g: &Graph, restrictions: &TurnRestrictions) -> Self {
+        let mut first_arc = vec![0];
+        let mut arc_tail = vec![];
+        let mut arc_head = vec![];
+        let mut arc_weight = vec![];
+        for (u, neighbours) in g.edges.iter().enumerate() {
+            for (i, &v) in neighbours.iter().enumerate() {
+                arc_tail.push(u);
+                arc_head.push(v);
+                arc_weight.push(g.weights[u][i]);
+            }
+            first_arc.push(arc_tail.len());
+        }
+
+        let mut graph = Graph::new(arc_tail.len());
+        for a in 0..arc_tail.len() {
+            let (from, via) = (arc_tail[a], arc_head[a]);
+            for b in first_arc[via]..first_arc[via + 1] {
+                if let Some(penalty) = restrictions.turn_cost(from, via, arc_head[b]) {
+                    graph.add_edge(a, b, arc_weight[b] + penalty);
+                }
+            }
+        }
+
+        Self {
+            graph,
+            arc_tail,
+            arc_head,
+            arc_weight,
+            first_arc,
+        }
+    }
+
+    // arc u -> v of the road graph, the first one when there are several
+    pub fn arc(&self, u: usize, v: usize) -> Option<usize> {
+        (self.first_arc[u]..self.first_arc[u + 1]).find(|&a| self.arc_head[a] == v)
+    }
+
+    // arcs leaving node that may be used, with their cost
+    pub fn arcs_from(&self, node: usize, edge_cost: &EdgeCost<'_>) -> Vec<(usize, f64)> {
+        (self.first_arc[node]..self.first_arc[node + 1])
+            .filter_map(|a| Some((a, edge_cost(node, self.arc_head[a], self.arc_weight[a])?)))
+            .collect()
+    }
+
+    // arcs reaching node, found through the reverse of the road graph
+    pub fn arcs_into(&self, node: usize, reverse: &Graph) -> Vec<usize> {
+        reverse.edges[node].iter().filter_map(|&u| self.arc(u, node)).collect()
+    }
+
+    // road nodes a path over the turn graph goes through
+    pub fn nodes(&self, arcs: &[usize]) -> Vec<usize> {
+        let mut nodes: Vec<usize> = arcs.first().map(|&a| self.arc_tail[a]).into_iter().collect();
+        nodes.extend(arcs.iter().map(|&a| self.arc_head[a]));
+        nodes
+    }
+
+    // edge cost for searches over the turn graph itself: the cost of the arc
+    // turned onto plus the turn penalty
+    pub fn turn_edge_cost<'a>(&'a self, edge_cost: &'a EdgeCost<'a>) -> impl Fn(usize, usize, f64) -> Option<f64> + 'a {
+        move |_, b, w| {
+            let cost = edge_cost(self.arc_tail[b], self.arc_head[b], self.arc_weight[b])?;
+            Some(cost + w - self.arc_weight[b])
+        }
+    }
+
+    // Settles the arcs reachable from src in order of cost, telling visit the
+    // node each of them reaches and at what cost, until visit returns true
+    // or the costs go over max_cost. arc_cost gives the cost of an arc entered
+    // at the given cost, and turn penalties are multiplied by penalty_scale.
+    fn settle_from(
+        &self,
+        workspace: &mut SearchWorkspace,
+        src: usize,
+        max_cost: f64,
+        arc_cost: &dyn Fn(usize, f64) -> Option<f64>,
+        penalty_scale: f64,
+        visit: &mut dyn FnMut(usize, f64) -> bool,
+    ) {
+        workspace.fit(self.graph.edges.len());
+        let space = &mut workspace.forward;
+        space.reset();
+        if visit(src, 0.0) {
+            return;
+        }
+        for a in self.first_arc[src]..self.first_arc[src + 1] {
+            let Some(w) = arc_cost(a, 0.0) else {
+                continue;
+            };
+            if w < space.dist(a) {
+                space.update(a, w, None, w);
+            }
+        }
+
+        while space.has_queued() {
+            let arc = space.pop();
+            if arc.distance > max_cost {
+                break;
+            }
+            space.settle(arc.id);
+            let elapsed = space.dist(arc.id);
+            if visit(self.arc_head[arc.id], elapsed) {
+                return;
+            }
+            for (i, &next) in self.graph.edges[arc.id].iter().enumerate() {
+                if space.is_settled(next) {
+                    continue;
+                }
+                let penalty = (self.graph.weights[arc.id][i] - self.arc_weight[next]) * penalty_scale;
+                let Some(w) = arc_cost(next, elapsed + penalty) else {
+                    continue;
+                };
+                let alt = elapsed + penalty + w;
+                if alt < space.dist(next) {
+                    space.update(next, alt, Some(arc.id), alt);
+                }
+            }
+        }
+    }
+
+    // costs from src to every target, None for the ones it cannot reach
+    // within max_cost; see settle_from for arc_cost and penalty_scale
+    pub fn one_to_many(
+        &self,
+        workspace: &mut SearchWorkspace,
+        src: usize,
+        targets: &[usize],
+        max_cost: f64,
+        arc_cost: &dyn Fn(usize, f64) -> Option<f64>,
+        penalty_scale: f64,
+    ) -> Vec<Option<f64>> {
+        let mut reached: HashMap<usize, f64> = HashMap::new();
+        let mut remaining: HashSet<usize> = targets.iter().copied().collect();
+        self.settle_from(workspace, src, max_cost, arc_cost, penalty_scale, &mut |node, cost| {
+            reached.entry(node).or_insert(cost);
+            remaining.remove(&node);
+            remaining.is_empty()
+        });
+        targets.iter().map(|target| reached.get(target).copied()).collect()
+    }
+
+    // searches over the turn graph starting on any arc leaving src and
+    // stopping at the first arc that reaches dest
+    fn search(
+        &self,
+        workspace: &mut SearchWorkspace,
+        src: usize,
+        dest: usize,
+        potential: &dyn Fn(usize) -> f64,
+        edge_cost: &EdgeCost<'_>,
+    ) -> Result<Route, RoutingError> {
+        if src == dest {
+            return Ok((0.0, vec![src]));
+        }
+        workspace.fit(self.graph.edges.len());
+        let space = &mut workspace.forward;
+        space.reset();
+        for a in self.first_arc[src]..self.first_arc[src + 1] {
+            let Some(w) = edge_cost(src, self.arc_head[a], self.arc_weight[a]) else {
+                continue;
+            };
+            if w < space.dist(a) {
+                space.update(a, w, None, w + potential(self.arc_head[a]));
+            }
+        }
+
+        while space.has_queued() {
+            let arc = space.pop();
+            if self.arc_head[arc.id] == dest {
+                let mut path = vec![src];
+                path.extend(space.path_to(arc.id).iter().map(|&a| self.arc_head[a]));
+                return Ok((space.dist(arc.id), path));
+            }
+            space.settle(arc.id);
+            for (i, &next) in self.graph.edges[arc.id].iter().enumerate() {
+                if space.is_settled(next) {
+                    continue;
+                }
+                // the turn arc weighs the arc it turns onto plus the turn penalty
+                let penalty = self.graph.weights[arc.id][i] - self.arc_weight[next];
+                let (via, to) = (self.arc_tail[next], self.arc_head[next]);
+                let Some(w) = edge_cost(via, to, self.arc_weight[next]) else {
+                    continue;
+                };
+                let alt = space.dist(arc.id) + w + penalty;
+                if alt < space.dist(next) {
+                    let key = alt + potential(self.arc_head[next]);
+                    space.update(next, alt, Some(arc.id), key);
+                }
+            }
+        }
+        Err(RoutingError::NoPath)
+    }
+}
+
+// Cheapest route from any of the source arcs to any of the target arcs. A
+// source comes with the cost of having driven it up to its head and a target
+// with the cost of the part of it driven after turning onto it, so both the
+// first and the last turn are checked. potential estimates the cost from a
+// node to the closest tail of a target. The route lists the nodes from the
+// head of the source to the tail of the target.
+pub fn turn_aware_multi_source_search(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    sources: &[(usize, f64)],
+    targets: &[(usize, f64)],
+    potential: &dyn Fn(usize) -> f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Route, RoutingError> {
+    workspace.fit(turn_graph.graph.edges.len());
+    let space = &mut workspace.forward;
+    space.reset();
+    for &(a, cost) in sources {
+        if cost < space.dist(a) {
+            space.update(a, cost, None, cost + potential(turn_graph.arc_head[a]));
+        }
+    }
+    let mut best: Option<(f64, usize)> = None;
+    while space.has_queued() {
+        if best.is_some_and(|(cost, _)| space.min_key() >= cost) {
+            break;
+        }
+        let arc = space.pop();
+        space.settle(arc.id);
+        for (i, &next) in turn_graph.graph.edges[arc.id].iter().enumerate() {
+            let penalty = turn_graph.graph.weights[arc.id][i] - turn_graph.arc_weight[next];
+            for &(_, cost) in targets.iter().filter(|&&(target, _)| target == next) {
+                let total = space.dist(arc.id) + penalty + cost;
+                if best.is_none_or(|(best, _)| total < best) {
+                    best = Some((total, arc.id));
+                }
+            }
+            if space.is_settled(next) {
+                continue;
+            }
+            let (via, to) = (turn_graph.arc_tail[next], turn_graph.arc_head[next]);
+            let Some(w) = edge_cost(via, to, turn_graph.arc_weight[next]) else {
+                continue;
+            };
+            let alt = space.dist(arc.id) + w + penalty;
+            if alt < space.dist(next) {
+                let key = alt + potential(to);
+                space.update(next, alt, Some(arc.id), key);
+            }
+        }
+    }
+    let (cost, last) = best.ok_or(RoutingError::NoPath)?;
+    let path = space.path_to(last).iter().map(|&a| turn_graph.arc_head[a]).collect();
+    Ok((cost, path))
+}
+
+pub fn turn_aware_dijkstra(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    src: usize,
+    dest: usize,
+) -> Result<Route, RoutingError> {
+    turn_graph.search(workspace, src, dest, &|_| 0.0, &|_, _, w| Some(w))
+}
+
+// turn penalties only add cost, so node heuristics stay admissible
+pub fn turn_aware_astar(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+) -> Result<Route, RoutingError> {
+    turn_aware_astar_with_filter(workspace, turn_graph, map, src, dest, heuristic, &|_, _| {
+        true
+    })
+}
+
+pub fn turn_aware_astar_with_filter(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+    filter: &EdgeFilter<'_>,
+) -> Result<Route, RoutingError> {
+    turn_graph.search(workspace, src, dest, &|v| heuristic(map, v, dest), &|u, v, w| filter(u, v).then_some(w))
+}
+
+pub fn turn_aware_astar_with_edge_costs(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    map: &HashMap<usize, Coordinate>,
+    src: usize,
+    dest: usize,
+    heuristic: &Heuristic<'_>,
+    edge_cost: &EdgeCost<'_>,
+) -> Result<Route, RoutingError> {
+    turn_graph.search(workspace, src, dest, &|v| heuristic(map, v, dest), edge_cost)
+}
+
+// same as one_to_many, but every turn must be allowed and costs its penalty
+pub fn turn_aware_one_to_many(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    src: usize,
+    targets: &[usize],
+    max_cost: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Vec<Option<f64>> {
+    let arc_cost = |a: usize, _| {
+        edge_cost(turn_graph.arc_tail[a], turn_graph.arc_head[a], turn_graph.arc_weight[a])
+    };
+    turn_graph.one_to_many(workspace, src, targets, max_cost, &arc_cost, 1.0)
+}
+
+// same as bounded_dijkstra, but every turn must be allowed and costs its penalty
+pub fn turn_aware_bounded_dijkstra(
+    workspace: &mut SearchWorkspace,
+    turn_graph: &TurnGraph,
+    src: usize,
+    max_cost: f64,
+    edge_cost: &EdgeCost<'_>,
+) -> Vec<(usize, f64)> {
+    let arc_cost = |a: usize, _| {
+        edge_cost(turn_graph.arc_tail[a], turn_graph.arc_head[a], turn_graph.arc_weight[a])
+    };
+    let mut reached = vec![];
+    let mut seen = HashSet::new();
+    turn_graph.settle_from(workspace, src, max_cost, &arc_cost, 1.0, &mut |node, cost| {
+        if seen.insert(node) {
+            reached.push((node, cost));
+        }
+        false
+    });
+    reached
+}
+
+// restrictions file lines are `from via to` for a forbidden turn, or
+// `from via to penalty` for a turn that costs extra
+// turn penalties only ever add cost, which keeps the searches and their
+// heuristics exact
+fn valid_penalty(penalty: f64) -> bool {
+    penalty.is_finite() && penalty >= 0.0
+}
+
+// U_TURN_PENALTY: unset for DEFAULT_U_TURN_PENALTY, "forbidden" to rule
+// U-turns out, or the penalty
+pub fn parse_u_turn_penalty(value: Option<&str>) -> Result<Option<f64>, Box<dyn Error>> {
+    match value {
+        None => Ok(Some(DEFAULT_U_TURN_PENALTY)),
+        Some("forbidden") => Ok(None),
+        Some(value) => {
+            let penalty: f64 = value.parse()?;
+            if !valid_penalty(penalty) {
+                return Err(format!("Invalid U-turn penalty {}", value).into());
+            }
+            Ok(Some(penalty))
+        }
+    }
+}
+
+pub fn parse_turn_restrictions(
+    content: &str,
+    u_turn_penalty: Option<f64>,
+) -> Result<TurnRestrictions, Box<dyn Error>> {
+    let mut restrictions = TurnRestrictions {
+        u_turn_penalty,
+        ..Default::default()
+    };
+    for (number, line) in content.lines().enumerate() {
+        let mut split_line = line.split_whitespace();
+        let Some(from) = split_line.next() else {
+            continue;
+        };
+        let from: usize = from.parse()?;
+        let via: usize = split_line.next().ok_or("Missing via node")?.parse()?;
+        let to: usize = split_line.next().ok_or("Missing to node")?.parse()?;
+        match split_line.next() {
+            Some(penalty) => {
+                let penalty: f64 = penalty.parse()?;
+                if !valid_penalty(penalty) {
+                    return Err(format!("Invalid turn penalty on line {}: {}", number + 1, line).into());
+                }
+                if penalty >= INFINITY {
+                    restrictions.forbidden.insert((from, via, to));
+                } else {
+                    restrictions.penalties.insert((from, via, to), penalty);
+                }
+            }
+            None => {
+                restrictions.forbidden.insert((from, via, to));
+            }
+        }
+    }
+    Ok(restrictions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // crossing at node 1: west 0, east 2, north 3, south 4, all two-way
+    fn crossing() -> Graph {
+        let mut g = Graph::new(5);
+        for (u, v, w) in [
+            (0, 1, 1.0),
+            (1, 2, 1.0),
+            (1, 3, 1.0),
+            (1, 4, 1.0),
+            (2, 3, 5.0),
+        ] {
+            g.add_edge(u, v, w);
+            g.add_edge(v, u, w);
+        }
+        g
+    }
+
+    #[test]
+    fn test_turn_graph_without_restrictions() {
+        let g = crossing();
+        let restrictions = TurnRestrictions {
+            u_turn_penalty: Some(0.0),
+            ..Default::default()
+        };
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let route = turn_aware_dijkstra(&mut workspace, &turn_graph, 0, 3).unwrap();
+        assert_eq!(route, (2.0, vec![0, 1, 3]));
+    }
+
+    #[test]
+    fn test_forbidden_turn_forces_detour() {
+        let g = crossing();
+        let restrictions = parse_turn_restrictions("0 1 3\n", None).unwrap();
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let route = turn_aware_dijkstra(&mut workspace, &turn_graph, 0, 3).unwrap();
+        // straight on to 2, then up the side street
+        assert_eq!(route, (7.0, vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn test_u_turns() {
+        let g = crossing();
+        let mut restrictions = parse_turn_restrictions("0 1 3\n", Some(1.0)).unwrap();
+        restrictions.penalties.insert((0, 1, 2), 10.0);
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        // from the west: turn right to 4, U-turn there, then straight on to 3
+        let route = turn_aware_dijkstra(&mut workspace, &turn_graph, 0, 3).unwrap();
+        assert_eq!(route, (5.0, vec![0, 1, 4, 1, 3]));
+
+        restrictions.u_turn_penalty = None;
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let route = turn_aware_dijkstra(&mut workspace, &turn_graph, 0, 3).unwrap();
+        assert_eq!(route, (17.0, vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn test_multi_source_search_checks_first_and_last_turn() {
+        let g = crossing();
+        let mut restrictions = parse_turn_restrictions("0 1 3\n", Some(1.0)).unwrap();
+        restrictions.penalties.insert((0, 1, 2), 10.0);
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let cost = |_: usize, _: usize, w: f64| Some(w);
+        // halfway along 0 -> 1 to a quarter of the way along 1 -> 3
+        let from_west = [(turn_graph.arc(0, 1).unwrap(), 0.5)];
+        let north = [(turn_graph.arc(1, 3).unwrap(), 0.25)];
+        let route = turn_aware_multi_source_search(&mut workspace, &turn_graph, &from_west, &north, &|_| 0.0, &cost).unwrap();
+        // the left turn is forbidden: right to 4, U-turn there, then straight on
+        assert_eq!(route, (0.5 + 1.0 + 1.0 + 1.0 + 0.25, vec![1, 4, 1]));
+
+        // coming from the south the turn is allowed
+        let from_south = [(turn_graph.arc(4, 1).unwrap(), 0.5)];
+        let route = turn_aware_multi_source_search(&mut workspace, &turn_graph, &from_south, &north, &|_| 0.0, &cost).unwrap();
+        assert_eq!(route, (0.75, vec![1]));
+        // and with the detour closed the west has no way in
+        let closed = turn_aware_multi_source_search(&mut workspace, &turn_graph, &from_west, &north, &|_| 0.0, &|_, _, _| None);
+        assert!(closed.is_err());
+    }
+
+    #[test]
+    fn test_one_to_many_and_bounded_search() {
+        let g = crossing();
+        let mut restrictions = parse_turn_restrictions("0 1 3\n", Some(1.0)).unwrap();
+        restrictions.penalties.insert((0, 1, 2), 10.0);
+        restrictions.penalties.insert((4, 1, 2), 10.0);
+        let turn_graph = TurnGraph::new(&g, &restrictions);
+        let mut workspace = SearchWorkspace::new(g.edges.len());
+        let cost = |_: usize, _: usize, w: f64| Some(w);
+        // from the west 3 takes a U-turn at 4, and 2 another one at 3 to
+        // come back to 1 from the north
+        let costs = turn_aware_one_to_many(&mut workspace, &turn_graph, 0, &[3, 2, 0, 4], INFINITY, &cost);
+        assert_eq!(costs, vec![Some(5.0), Some(8.0), Some(0.0), Some(2.0)]);
+        let costs = turn_aware_one_to_many(&mut workspace, &turn_graph, 0, &[3, 4], 4.0, &cost);
+        assert_eq!(costs, vec![None, Some(2.0)]);
+
+        let reached = turn_aware_bounded_dijkstra(&mut workspace, &turn_graph, 0, 5.0, &cost);
+        assert_eq!(reached, vec![(0, 0.0), (1, 1.0), (4, 2.0), (3, 5.0)]);
+        let closed = |u: usize, _: usize, w: f64| (u != 1).then_some(w);
+        assert_eq!(turn_aware_bounded_dijkstra(&mut workspace, &turn_graph, 0, 5.0, &closed), vec![(0, 0.0), (1, 1.0)]);
+    }
+
+    #[test]
+    fn test_turn_penalty_in_file() {
+        let restrictions = parse_turn_restrictions("0 1 2 3.5\n2 1 0\n", None).unwrap();
+        assert_eq!(restrictions.turn_cost(0, 1, 2), Some(3.5));
+        assert_eq!(restrictions.turn_cost(2, 1, 0), None);
+        assert_eq!(restrictions.turn_cost(3, 1, 3), None);
+        assert_eq!(restrictions.turn_cost(3, 1, 2), Some(0.0));
+
+        for content in ["0 1 2 -5\n", "0 1 2 NaN\n", "3 1 3\n0 1 2 inf\n"] {
+            assert!(parse_turn_restrictions(content, None).is_err());
+        }
+        let error = parse_turn_restrictions("3 1 3\n0 1 2 -5\n", None).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid turn penalty on line 2: 0 1 2 -5");
+    }
+
+    #[test]
+    fn test_u_turn_penalty() {
+        assert_eq!(parse_u_turn_penalty(None).unwrap(), Some(DEFAULT_U_TURN_PENALTY));
+        assert_eq!(parse_u_turn_penalty(Some("forbidden")).unwrap(), None);
+        assert_eq!(parse_u_turn_penalty(Some("30")).unwrap(), Some(30.0));
+        for value in ["-5", "NaN", "inf", "many"] {
+            assert!(parse_u_turn_penalty(Some(value)).is_err());
+        }
+    }
+}
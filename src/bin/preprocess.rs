@@ -0,0 +1,39 @@
+// Builds the preprocessed files the server loads at startup, for every
+// profile whose environment variables name one. Run it again whenever the
+// arcs change:
+//     cargo run --release --bin preprocess
+use dotenvy::dotenv;
+use std::env;
+use tsp::{
+    algo::{
+        contraction_hierarchies::ContractionHierarchy,
+        landmarks::{LandmarkSelection, Landmarks},
+    },
+    utils::{self, profile::RoutingProfile},
+};
+
+fn main() {
+    dotenv().ok();
+    let coordinates_file = env::var("COORDINATES_FILE").unwrap();
+    for profile in RoutingProfile::ALL {
+        let var = |name: &str| env::var(format!("{}{}", profile.env_prefix(), name)).ok();
+        let Some(arcs_file) = var("ARCS_FILE") else {
+            continue;
+        };
+        let graph = utils::create_adjacency_list_from_files(&coordinates_file, &arcs_file).unwrap();
+
+        if let Some(hierarchy_file) = var("CH_FILE") {
+            println!("Building the {} contraction hierarchy into {}", profile.as_str(), hierarchy_file);
+            ContractionHierarchy::new(&graph).save(&hierarchy_file).unwrap();
+        }
+        if let Some(landmarks_file) = var("LANDMARKS_FILE") {
+            let count = var("LANDMARKS_COUNT")
+                .map(|count| count.parse().unwrap())
+                .unwrap_or(16);
+            println!("Selecting {} {} landmarks into {}", count, profile.as_str(), landmarks_file);
+            Landmarks::new(&graph, &graph.reverse(), count, LandmarkSelection::Avoid)
+                .save(&landmarks_file)
+                .unwrap();
+        }
+    }
+}
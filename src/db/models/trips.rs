@@ -16,7 +16,8 @@ pub struct Trip {
     pub path: serde_json::Value,
     pub distance: f64,
     pub completed: bool,
-    pub created_on: NaiveDateTime
+    pub created_on: NaiveDateTime,
+    pub profile: String,
 }
 
 #[derive(Insertable, Associations, Debug)]
@@ -30,4 +31,5 @@ pub struct NewTrip<'a> {
     pub distance: &'a f64,
     pub completed: &'a bool,
     pub created_on: &'a dsl::now,
+    pub profile: &'a str,
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use crate::db::users::get_user_by_id;
 use crate::utils::path::Path;
 use crate::schema;
 use crate::utils::trip::Location;
+use crate::utils::profile::RoutingProfile;
 use diesel::prelude::*;
 
 pub fn get_trips_by_user_id(query_id: &i32, page: i64) -> Result<Vec<Trip>, diesel::result::Error> {
@@ -22,12 +23,14 @@ pub fn get_trips_by_user_id(query_id: &i32, page: i64) -> Result<Vec<Trip>, dies
     Ok(results)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_trip(
         user_id: &i32,
         title: &String,
         locations: &Vec<Location>,
         path: &Path,
         distance: &f64,
+        profile: &RoutingProfile,
         completed: &bool,
         created_on: &diesel::dsl::now
     ) -> Result<Vec<Trip>, diesel::result::Error> {
@@ -40,6 +43,7 @@ pub fn create_trip(
             path: &serde_json::to_value(path).unwrap(),
             distance,
             completed,
+            profile: profile.as_str(),
             created_on,
         };
 
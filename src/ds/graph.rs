@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug)]
 pub struct Graph {
     pub edges: Vec<Vec<usize>>,
@@ -16,4 +18,74 @@ impl Graph {
         self.edges[u].push(v);
         self.weights[u].push(w);
     }
+
+    // graph with every arc flipped, used by the backward half of bidirectional searches
+    pub fn reverse(&self) -> Self {
+        let mut reversed = Self::new(self.edges.len());
+        for u in 0..self.edges.len() {
+            for (i, &v) in self.edges[u].iter().enumerate() {
+                reversed.add_edge(v, u, self.weights[u][i]);
+            }
+        }
+        reversed
+    }
+
+    pub fn fingerprint(&self) -> GraphFingerprint {
+        // FNV-1a
+        let mut checksum: u64 = 0xcbf29ce484222325;
+        let mut arcs = 0;
+        for (u, neighbours) in self.edges.iter().enumerate() {
+            for (i, &v) in neighbours.iter().enumerate() {
+                let arc = [u as u64, v as u64, self.weights[u][i].to_bits()];
+                for byte in arc.iter().flat_map(|word| word.to_le_bytes()) {
+                    checksum = (checksum ^ byte as u64).wrapping_mul(0x100000001b3);
+                }
+                arcs += 1;
+            }
+        }
+        GraphFingerprint {
+            nodes: self.edges.len(),
+            arcs,
+            checksum,
+        }
+    }
+}
+
+// identifies the graph a preprocessed file was built from, so a file left
+// over from other arcs is caught when it is loaded
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphFingerprint {
+    pub nodes: usize,
+    pub arcs: usize,
+    pub checksum: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse() {
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(0, 2, 4.0);
+        g.add_edge(1, 2, 2.0);
+        let r = g.reverse();
+        assert!(r.edges[0].is_empty());
+        assert_eq!(r.edges[1], vec![0]);
+        assert_eq!(r.edges[2], vec![0, 1]);
+        assert_eq!(r.weights[2], vec![4.0, 2.0]);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(1, 2, 2.0);
+        let fingerprint = g.fingerprint();
+        assert_eq!((fingerprint.nodes, fingerprint.arcs), (3, 2));
+        assert_eq!(fingerprint, g.reverse().reverse().fingerprint());
+        g.weights[1][0] = 2.5;
+        assert_ne!(fingerprint, g.fingerprint());
+    }
 }
@@ -1,169 +1,376 @@
-use std::{ops::{Add, Sub, Mul}, fmt, cmp::Ordering};
 use pdqselect::select_by;
+use std::{f64::consts::FRAC_PI_2, marker::PhantomData};
 
+const EARTH_RADIUS: f64 = 6371000.0;
+
+// How the tree measures distances. axis_bound is a lower bound of the distance
+// from target to any point on the other side of split along axis, which lets a
+// search skip the subtrees that cannot hold anything closer.
+pub trait Metric<const K: usize> {
+    fn distance(a: &[f64; K], b: &[f64; K]) -> f64;
+    fn axis_bound(target: &[f64; K], axis: usize, split: f64) -> f64;
+}
+
+// straight line distance
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Euclidean;
+
+impl<const K: usize> Metric<K> for Euclidean {
+    fn distance(a: &[f64; K], b: &[f64; K]) -> f64 {
+        a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt()
+    }
+
+    fn axis_bound(target: &[f64; K], axis: usize, split: f64) -> f64 {
+        (target[axis] - split).abs()
+    }
+}
+
+// great-circle distance in metres between [lat, lng] points in degrees
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Haversine;
+
+impl Metric<2> for Haversine {
+    fn distance(a: &[f64; 2], b: &[f64; 2]) -> f64 {
+        let (lat_a, lat_b) = (a[0].to_radians(), b[0].to_radians());
+        let d_lat = lat_b - lat_a;
+        let d_lng = (b[1] - a[1]).to_radians();
+        let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+    }
+
+    fn axis_bound(target: &[f64; 2], axis: usize, split: f64) -> f64 {
+        if axis == 0 {
+            // the shortest way to another latitude is along a meridian
+            return EARTH_RADIUS * (target[0] - split).abs().to_radians();
+        }
+        // the other side is reached crossing either the split meridian or the antimeridian
+        meridian_distance(target, split).min(meridian_distance(target, 180.0))
+    }
+}
+
+// distance from point to the meridian at lng, which ends at the poles
+fn meridian_distance(point: &[f64; 2], lng: f64) -> f64 {
+    let lat = point[0].to_radians();
+    let d_lng = (lng - point[1]).to_radians();
+    if d_lng.cos() < 0.0 {
+        // the meridian is on the far side of the globe, the closest pole is the nearest point
+        return EARTH_RADIUS * (FRAC_PI_2 - lat.abs());
+    }
+    EARTH_RADIUS * (d_lng.sin().abs() * lat.cos()).min(1.0).asin()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor<'a, T, const K: usize> {
+    pub point: &'a [f64; K],
+    pub item: &'a T,
+    pub distance: f64,
+}
+
+// Kd-tree over K dimensional points, each carrying an item such as a node id.
+// Points are inserted with the logarithmic method: the tree is a list of static
+// blocks of decreasing size, and a new point starts a block of its own that is
+// merged with the previous ones while it is at least as large. Removed points
+// are only marked, and everything is rebuilt once they outnumber the live ones.
+#[derive(Debug, Clone)]
+pub struct KdTree<T, M = Haversine, const K: usize = 2> {
+    blocks: Vec<Block<T, K>>,
+    len: usize,
+    removed: usize,
+    metric: PhantomData<M>,
+}
+
+// A static kd-tree. The points are kept in one array laid out as an implicit
+// tree: the median of every range along its axis is in the middle of the range.
 #[derive(Debug, Clone)]
-pub struct KdTree<T> where T: Copy + PartialEq, {
-    pub root: Vec<Vec<T>>,
+struct Block<T, const K: usize> {
+    points: Vec<([f64; K], T)>,
+    removed: Vec<bool>,
 }
 
-impl<T> KdTree<T> where T: Copy + PartialEq + PartialOrd + fmt::Debug + Sub<Output = T> + Mul<Output = T> + Add<Output = T> {
-    pub fn new(mut arr: Vec<Vec<T>>) -> Self {
-        Self::sort_kdtree(&mut arr, 0);
+impl<T, M: Metric<K>, const K: usize> KdTree<T, M, K> {
+    pub fn new(points: Vec<([f64; K], T)>) -> Self {
+        let len = points.len();
+        let blocks = if points.is_empty() { vec![] } else { vec![Block::new(points)] };
         Self {
-            root: arr.to_owned(),
+            blocks,
+            len,
+            removed: 0,
+            metric: PhantomData,
         }
     }
 
-    pub fn closest<'a>(target:&'a Vec<T>, point_a: &'a Vec<T>, point_b: &'a Vec<T>) -> Vec<T> {
-        let target_a = Self::distance(target, point_a);
-        let target_b = Self::distance(target, point_b);
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        if target_a < target_b { point_a.clone() }
-        else { point_b.clone() }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
-    pub fn distance<'a>(target: &'a Vec<T>, point: &'a Vec<T>) -> T {
-        let x: T = (target[0] - point[0])*(target[0] - point[0]);
-        let y: T = (target[1] - point[1])*(target[1] - point[1]);
-        x+y
+    pub fn iter(&self) -> impl Iterator<Item = (&[f64; K], &T)> {
+        self.blocks.iter().flat_map(|block| {
+            block
+                .points
+                .iter()
+                .zip(&block.removed)
+                .filter(|(_, removed)| !**removed)
+                .map(|((point, item), _)| (point, item))
+        })
     }
 
-    pub fn compare(a: &Vec<T>, b: &Vec<T>, k: usize) -> Ordering {
-        if a[k] < b[k] { Ordering::Less }
-        else { Ordering::Greater }
+    pub fn insert(&mut self, point: [f64; K], item: T) {
+        self.blocks.push(Block::new(vec![(point, item)]));
+        while self.blocks.len() > 1 {
+            let last = self.blocks.len() - 1;
+            if self.blocks[last].points.len() < self.blocks[last - 1].points.len() {
+                break;
+            }
+            let (a, b) = (self.blocks.pop().unwrap(), self.blocks.pop().unwrap());
+            self.removed -= a.removed_count() + b.removed_count();
+            let points = a.into_live().chain(b.into_live()).collect();
+            self.blocks.push(Block::new(points));
+        }
+        self.len += 1;
     }
 
-    pub fn sort_kdtree(array: &mut [Vec<T>], k: usize) {
-        if array.len() > 1 {
-            select_by(array, array.len()/2, |a , b| Self::compare(&a, &b, k));
-            let size = array.len();
-            let middle: usize = if size%2 != 0 { size/2 } else { size/2 - 1 };
+    // removes one point at exactly point carrying item, false if there is none
+    pub fn remove(&mut self, point: &[f64; K], item: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        for block in self.blocks.iter_mut() {
+            if let Some(index) = block.find(0, block.points.len(), 0, point, item) {
+                block.removed[index] = true;
+                self.len -= 1;
+                self.removed += 1;
+                if self.removed > self.len {
+                    self.rebuild();
+                }
+                return true;
+            }
+        }
+        false
+    }
 
-            Self::sort_kdtree(&mut array[..middle], (k+1)%2);
-            Self::sort_kdtree(&mut array[middle + 1..], (k+1)%2);
+    // merges every block into one, dropping the removed points
+    fn rebuild(&mut self) {
+        let points: Vec<([f64; K], T)> = self.blocks.drain(..).flat_map(Block::into_live).collect();
+        if !points.is_empty() {
+            self.blocks.push(Block::new(points));
         }
+        self.removed = 0;
     }
 
-    pub fn get_nearest_neighbor(&self, target: &Vec<T>) -> Result<Vec<T>, ()> {
-        self.nearest_neighbor(&self.root, target, 0)
+    pub fn get_nearest_neighbor(&self, target: &[f64; K]) -> Option<Neighbor<'_, T, K>> {
+        self.get_n_nearest_neighbor(target, 1).into_iter().next()
     }
 
-    pub fn nearest_neighbor(&self, arr: &[Vec<T>], target: &Vec<T>, k: usize) -> Result<Vec<T>, ()> {
-        if arr.len() == 1 { return Ok(arr[0].clone()) }
+    // the n points closest to target, closest first
+    pub fn get_n_nearest_neighbor(&self, target: &[f64; K], n: usize) -> Vec<Neighbor<'_, T, K>> {
+        let mut bests = Vec::with_capacity(n + 1);
+        if n > 0 {
+            for (b, block) in self.blocks.iter().enumerate() {
+                block.n_nearest_neighbor::<M>(b, 0, block.points.len(), 0, target, n, &mut bests);
+            }
+        }
+        bests.into_iter().map(|(distance, at)| self.neighbor(at, distance)).collect()
+    }
 
-        let size = arr.len();
-        let middle: usize = if size%2 != 0 { size/2 } else { size/2 - 1 };
-        let first: &[Vec<T>];
-        let second: &[Vec<T>];
-        if target[k] < arr[middle][k] {
-            first = &arr[..middle];
-            second = &arr[middle + 1..];
-        } else {
-            first = &arr[middle + 1..];
-            second = &arr[..middle];
+    // every point within radius of target, closest first
+    pub fn within_radius(&self, target: &[f64; K], radius: f64) -> Vec<Neighbor<'_, T, K>> {
+        let mut found = vec![];
+        for (b, block) in self.blocks.iter().enumerate() {
+            block.radius_search::<M>(b, 0, block.points.len(), 0, target, radius, &mut found);
         }
+        found.sort_by(|a, b| a.0.total_cmp(&b.0));
+        found.into_iter().map(|(distance, at)| self.neighbor(at, distance)).collect()
+    }
 
-        let mut option: Vec<T> = if first.len() > 0 { self.nearest_neighbor(first, target, (k+1)%2).unwrap() } else { arr[middle].clone() };
-        let mut best: Vec<T> = Self::closest(target, &arr[middle], &option);
+    // every point with min[i] <= point[i] <= max[i] on all axes, in no particular order
+    pub fn within_bbox(&self, min: &[f64; K], max: &[f64; K]) -> Vec<(&[f64; K], &T)> {
+        let mut found = vec![];
+        for (b, block) in self.blocks.iter().enumerate() {
+            block.bbox_search(b, 0, block.points.len(), 0, min, max, &mut found);
+        }
+        found
+            .into_iter()
+            .map(|(b, index)| {
+                let (point, item) = &self.blocks[b].points[index];
+                (point, item)
+            })
+            .collect()
+    }
 
-        let r: T = Self::distance(target, &best);
-        let rc: T = target[k] - arr[middle][k];
+    // at is (block, index)
+    fn neighbor(&self, at: (usize, usize), distance: f64) -> Neighbor<'_, T, K> {
+        let (point, item) = &self.blocks[at.0].points[at.1];
+        Neighbor { point, item, distance }
+    }
+}
 
-        if r >= (rc*rc) {
-            option =  if second.len() > 0 { self.nearest_neighbor(second, target, (k+1)%2).unwrap() } else { best.clone() };
-            best = Self::closest(target, &option, &best);
-        }
+impl<T, const K: usize> Block<T, K> {
+    fn new(mut points: Vec<([f64; K], T)>) -> Self {
+        Self::sort_kdtree(&mut points, 0);
+        let removed = vec![false; points.len()];
+        Self { points, removed }
+    }
 
-        Ok(best)
+    fn removed_count(&self) -> usize {
+        self.removed.iter().filter(|removed| **removed).count()
     }
 
-    pub fn get_n_nearest_neighbor(&self, target: &Vec<T>, n: usize) -> Result<Vec<Vec<T>>, ()> {
-        let mut bests: Vec<(Vec<T>, T)> = Vec::with_capacity(n);
-        let _ = self.n_nearest_neighbor(&self.root, target, 0, &mut bests);
-        bests.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-        let results = bests.iter().map(|x| x.0.to_owned()).collect::<Vec<_>>();
-        Ok(results)
+    fn into_live(self) -> impl Iterator<Item = ([f64; K], T)> {
+        self.points.into_iter().zip(self.removed).filter(|(_, removed)| !removed).map(|(entry, _)| entry)
     }
 
-    pub fn save_best(&self, best: &Vec<T>, r: T, bests: &mut Vec<(Vec<T>, T)>) {
-        for i in 0..bests.len() {
-            if bests[i].0[2] == best[2] {
-                return;
-            } else {
-            }
-        }
-        if bests.len() < bests.capacity() {
-            let tuple = (best.clone(), r.clone());
-            bests.push(tuple.clone());
-        } else {
-            bests.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-            bests.reverse();
-            for i in 0..bests.len() {
-                if bests[i].1 > r {
-                    bests[i] = (best.clone(), r.clone());
-                    break;
-                }
-            }
+    fn sort_kdtree(points: &mut [([f64; K], T)], axis: usize) {
+        if points.len() > 1 {
+            let middle = points.len() / 2;
+            select_by(points, middle, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+            Self::sort_kdtree(&mut points[..middle], (axis + 1) % K);
+            Self::sort_kdtree(&mut points[middle + 1..], (axis + 1) % K);
         }
     }
 
-    pub fn n_nearest_neighbor(&self, arr: &[Vec<T>], target: &Vec<T>, k: usize, bests: &mut Vec<(Vec<T>, T)>) -> Result<Vec<T>, ()> {
-        if arr.len() == 1 { 
-            let best = arr[0].clone();
-            let r = Self::distance(target, &arr[0]);
-            self.save_best(&best, r, bests);
-            return Ok(arr[0].clone())
+    // index of a live entry equal to (point, item)
+    fn find(&self, start: usize, end: usize, axis: usize, point: &[f64; K], item: &T) -> Option<usize>
+    where
+        T: PartialEq,
+    {
+        if start >= end {
+            return None;
+        }
+        let middle = start + (end - start) / 2;
+        let (split, split_item) = &self.points[middle];
+        if !self.removed[middle] && split == point && split_item == item {
+            return Some(middle);
         }
+        let next = (axis + 1) % K;
+        let mut found = None;
+        if point[axis] <= split[axis] {
+            found = self.find(start, middle, next, point, item);
+        }
+        if found.is_none() && split[axis] <= point[axis] {
+            found = self.find(middle + 1, end, next, point, item);
+        }
+        found
+    }
 
+    // bests holds (distance, (block, index)) sorted by distance, at most n of them
+    #[allow(clippy::too_many_arguments)]
+    fn n_nearest_neighbor<M: Metric<K>>(
+        &self,
+        block: usize,
+        start: usize,
+        end: usize,
+        axis: usize,
+        target: &[f64; K],
+        n: usize,
+        bests: &mut Vec<(f64, (usize, usize))>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = start + (end - start) / 2;
+        let split = &self.points[middle].0;
+        let distance = M::distance(target, split);
+        if !self.removed[middle] && (bests.len() < n || distance < bests[bests.len() - 1].0) {
+            let position = bests.partition_point(|best| best.0 <= distance);
+            bests.insert(position, (distance, (block, middle)));
+            bests.truncate(n);
+        }
 
-        let size = arr.len();
-        let middle: usize = if size%2 != 0 { size/2 } else { size/2 - 1 };
-        let first: &[Vec<T>];
-        let second: &[Vec<T>];
-        if target[k] < arr[middle][k] {
-            first = &arr[..middle];
-            second = &arr[middle + 1..];
+        let next = (axis + 1) % K;
+        let (near, far) = if target[axis] < split[axis] {
+            ((start, middle), (middle + 1, end))
         } else {
-            first = &arr[middle + 1..];
-            second = &arr[..middle];
+            ((middle + 1, end), (start, middle))
+        };
+        self.n_nearest_neighbor::<M>(block, near.0, near.1, next, target, n, bests);
+        if bests.len() < n || M::axis_bound(target, axis, split[axis]) <= bests[bests.len() - 1].0 {
+            self.n_nearest_neighbor::<M>(block, far.0, far.1, next, target, n, bests);
         }
-        
-        let r: T = Self::distance(target, &arr[middle]);
-        self.save_best(&arr[middle], r, bests);
-
-        let mut option: Vec<T> = if first.len() > 0 { self.n_nearest_neighbor(first, target, (k+1)%2, bests).unwrap() } else { arr[middle].clone() };
-        let r: T = Self::distance(target, &option);
-        self.save_best(&option, r, bests);
+    }
 
-        let mut best: Vec<T> = Self::closest(target, &arr[middle], &option);
-        let r: T = Self::distance(target, &best);
-        self.save_best(&best, r, bests);
-        
-        let rc: T = target[k] - arr[middle][k];
-        
+    #[allow(clippy::too_many_arguments)]
+    fn radius_search<M: Metric<K>>(
+        &self,
+        block: usize,
+        start: usize,
+        end: usize,
+        axis: usize,
+        target: &[f64; K],
+        radius: f64,
+        found: &mut Vec<(f64, (usize, usize))>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = start + (end - start) / 2;
+        let split = &self.points[middle].0;
+        let distance = M::distance(target, split);
+        if !self.removed[middle] && distance <= radius {
+            found.push((distance, (block, middle)));
+        }
 
-        if r >= (rc*rc) {
-            option =  if second.len() > 0 { self.n_nearest_neighbor(second, target, (k+1)%2, bests).unwrap() } else { best.clone() };
-            let r: T = Self::distance(target, &option);
-            self.save_best(&option, r, bests);
+        let next = (axis + 1) % K;
+        let (near, far) = if target[axis] < split[axis] {
+            ((start, middle), (middle + 1, end))
+        } else {
+            ((middle + 1, end), (start, middle))
+        };
+        self.radius_search::<M>(block, near.0, near.1, next, target, radius, found);
+        if M::axis_bound(target, axis, split[axis]) <= radius {
+            self.radius_search::<M>(block, far.0, far.1, next, target, radius, found);
+        }
+    }
 
-            best = Self::closest(target, &option, &best);
-            let r: T = Self::distance(target, &best);
-            self.save_best(&best, r, bests);
+    #[allow(clippy::too_many_arguments)]
+    fn bbox_search(
+        &self,
+        block: usize,
+        start: usize,
+        end: usize,
+        axis: usize,
+        min: &[f64; K],
+        max: &[f64; K],
+        found: &mut Vec<(usize, usize)>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = start + (end - start) / 2;
+        let split = &self.points[middle].0;
+        if !self.removed[middle] && (0..K).all(|i| min[i] <= split[i] && split[i] <= max[i]) {
+            found.push((block, middle));
         }
 
-        Ok(best)
+        // points equal to the split on this axis can be on either side
+        let next = (axis + 1) % K;
+        if min[axis] <= split[axis] {
+            self.bbox_search(block, start, middle, next, min, max, found);
+        }
+        if split[axis] <= max[axis] {
+            self.bbox_search(block, middle + 1, end, next, min, max, found);
+        }
     }
-
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
     use crate::utils::create_kd_tree_from_file;
     use dotenvy::dotenv;
+    use rand::Rng;
     use std::env;
 
+    fn brute_force<M: Metric<K>, const K: usize>(points: &[([f64; K], usize)], target: &[f64; K], n: usize) -> Vec<f64> {
+        let mut distances: Vec<f64> = points.iter().map(|(point, _)| M::distance(target, point)).collect();
+        distances.sort_by(|a, b| a.total_cmp(b));
+        distances.truncate(n);
+        distances
+    }
+
     #[test]
     fn test_n_nearest_neighbor_kd_tree() {
         dotenv().ok();
@@ -171,14 +378,140 @@ mod tests {
         let coordinates = env::var("COORDINATES_FILE").unwrap();
         let kd_tree = create_kd_tree_from_file(&coordinates).unwrap();
 
-        let _result = kd_tree.get_nearest_neighbor(&[4.665179, -74.063324].to_vec()).unwrap();
-        let results = kd_tree.get_n_nearest_neighbor(&[4.665179, -74.063324].to_vec(), 100).unwrap();
-        
-        
+        let _result = kd_tree.get_nearest_neighbor(&[4.665179, -74.063324]).unwrap();
+        let results = kd_tree.get_n_nearest_neighbor(&[4.665179, -74.063324], 100);
+
         println!("# results: {:?}", results.len());
-        for i in 0..results.len() {
-            println!("{:?}", results[i]);
+        for result in results.iter() {
+            println!("{:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_haversine_nearest_matches_brute_force() {
+        let mut rng = rand::thread_rng();
+        let points: Vec<([f64; 2], usize)> = (0..500)
+            .map(|id| ([rng.gen_range(-80.0..80.0), rng.gen_range(-180.0..180.0)], id))
+            .collect();
+        let tree: KdTree<usize> = KdTree::new(points.clone());
+        assert_eq!(tree.len(), 500);
+        // includes targets close to the antimeridian and the poles
+        let targets = [[4.6, -74.0], [0.0, 179.9], [0.0, -179.9], [89.0, 10.0], [-85.0, -170.0]];
+        for target in targets {
+            let found: Vec<f64> = tree.get_n_nearest_neighbor(&target, 10).iter().map(|n| n.distance).collect();
+            assert_eq!(found, brute_force::<Haversine, 2>(&points, &target, 10));
+            let nearest = tree.get_nearest_neighbor(&target).unwrap();
+            assert_eq!(points[*nearest.item].0, *nearest.point);
+        }
+    }
+
+    #[test]
+    fn test_within_radius_matches_brute_force() {
+        let mut rng = rand::thread_rng();
+        let points: Vec<([f64; 2], usize)> = (0..1000)
+            .map(|id| ([rng.gen_range(4.5..4.8), rng.gen_range(-74.2..-74.0)], id))
+            .collect();
+        let tree: KdTree<usize> = KdTree::new(points.clone());
+        for _ in 0..20 {
+            let target = [rng.gen_range(4.5..4.8), rng.gen_range(-74.2..-74.0)];
+            let radius = rng.gen_range(0.0..5000.0);
+            let found = tree.within_radius(&target, radius);
+            let mut expected: Vec<usize> = points
+                .iter()
+                .filter(|(point, _)| Haversine::distance(&target, point) <= radius)
+                .map(|(_, id)| *id)
+                .collect();
+            assert!(found.windows(2).all(|pair| pair[0].distance <= pair[1].distance));
+            let mut ids: Vec<usize> = found.iter().map(|n| *n.item).collect();
+            ids.sort();
+            expected.sort();
+            assert_eq!(ids, expected);
+        }
+        assert!(tree.within_radius(&[0.0, 0.0], 1000.0).is_empty());
+    }
+
+    #[test]
+    fn test_within_bbox_matches_brute_force() {
+        let mut rng = rand::thread_rng();
+        // a coarse grid so that many points lie exactly on the box edges and splits
+        let points: Vec<([f64; 3], usize)> = (0..1000)
+            .map(|id| ([rng.gen_range(0..10) as f64, rng.gen_range(0..10) as f64, rng.gen_range(0..10) as f64], id))
+            .collect();
+        let tree: KdTree<usize, Euclidean, 3> = KdTree::new(points.clone());
+        for _ in 0..20 {
+            let a = [rng.gen_range(0..10) as f64, rng.gen_range(0..10) as f64, rng.gen_range(0..10) as f64];
+            let b = [rng.gen_range(0..10) as f64, rng.gen_range(0..10) as f64, rng.gen_range(0..10) as f64];
+            let min = [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])];
+            let max = [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])];
+            let mut ids: Vec<usize> = tree.within_bbox(&min, &max).into_iter().map(|(_, id)| *id).collect();
+            let mut expected: Vec<usize> = points
+                .iter()
+                .filter(|(point, _)| (0..3).all(|i| min[i] <= point[i] && point[i] <= max[i]))
+                .map(|(_, id)| *id)
+                .collect();
+            ids.sort();
+            expected.sort();
+            assert_eq!(ids, expected);
         }
+        assert!(tree.within_bbox(&[5.0; 3], &[4.0; 3]).is_empty());
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_insert_and_remove_match_brute_force() {
+        let mut rng = rand::thread_rng();
+        let mut tree: KdTree<usize, Euclidean, 2> = KdTree::new(vec![]);
+        let mut points: Vec<([f64; 2], usize)> = vec![];
+        for id in 0..2000 {
+            // a coarse grid so that points repeat
+            let point = [rng.gen_range(0..50) as f64, rng.gen_range(0..50) as f64];
+            if !points.is_empty() && rng.gen_bool(0.3) {
+                let (point, id) = points.swap_remove(rng.gen_range(0..points.len()));
+                assert!(tree.remove(&point, &id));
+                assert!(!tree.remove(&point, &id));
+            } else {
+                tree.insert(point, id);
+                points.push((point, id));
+            }
+            assert_eq!(tree.len(), points.len());
+            // the logarithmic method keeps O(log n) blocks
+            assert!(tree.blocks.len() <= 2 * (usize::BITS - points.len().leading_zeros()) as usize + 1);
+
+            if id % 100 == 0 {
+                let target = [rng.gen_range(0.0..50.0), rng.gen_range(0.0..50.0)];
+                let found: Vec<f64> = tree.get_n_nearest_neighbor(&target, 5).iter().map(|n| n.distance).collect();
+                assert_eq!(found, brute_force::<Euclidean, 2>(&points, &target, 5));
+                let mut ids: Vec<usize> = tree.within_radius(&target, 8.0).iter().map(|n| *n.item).collect();
+                let mut expected: Vec<usize> = points
+                    .iter()
+                    .filter(|(point, _)| Euclidean::distance(&target, point) <= 8.0)
+                    .map(|(_, id)| *id)
+                    .collect();
+                ids.sort();
+                expected.sort();
+                assert_eq!(ids, expected);
+            }
+        }
+        let mut ids: Vec<usize> = tree.iter().map(|(_, id)| *id).collect();
+        let mut expected: Vec<usize> = points.iter().map(|(_, id)| *id).collect();
+        ids.sort();
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_euclidean_nearest_in_three_dimensions() {
+        let mut rng = rand::thread_rng();
+        let points: Vec<([f64; 3], usize)> = (0..300)
+            .map(|id| ([rng.gen_range(0.0..10.0), rng.gen_range(0.0..10.0), rng.gen_range(0.0..10.0)], id))
+            .collect();
+        let tree: KdTree<usize, Euclidean, 3> = KdTree::new(points.clone());
+        for _ in 0..20 {
+            let target = [rng.gen_range(0.0..10.0), rng.gen_range(0.0..10.0), rng.gen_range(0.0..10.0)];
+            let found: Vec<f64> = tree.get_n_nearest_neighbor(&target, 7).iter().map(|n| n.distance).collect();
+            assert_eq!(found, brute_force::<Euclidean, 3>(&points, &target, 7));
+        }
+        let empty: KdTree<usize, Euclidean, 3> = KdTree::new(vec![]);
+        assert!(empty.get_nearest_neighbor(&[0.0; 3]).is_none());
+        assert!(tree.get_n_nearest_neighbor(&[0.0; 3], 0).is_empty());
+    }
+}
@@ -3,3 +3,4 @@ pub mod linked_list;
 pub mod priority_queue;
 pub mod queue;
 pub mod kdtree;
+pub mod rtree;
@@ -26,6 +26,15 @@ impl<T: Prioritiness + Copy> MinHeap<T> {
         self.heap.is_empty()
     }
 
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.internal_map.clear();
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.internal_map.contains_key(&id)
+    }
+
     pub fn insert(&mut self, el: T) {
         self.heap.push(el);
         self.internal_map.insert(el.id(), self.heap.len() - 1);
@@ -195,4 +204,17 @@ mod tests {
         p.extract_min();
         p.extract_min();
     }
+
+    #[test]
+    fn test_priority_queue_contains() {
+        let mut p: MinHeap<Example> = MinHeap::new();
+        p.insert(Example {
+            priority: 1.0,
+            id: 7,
+        });
+        assert!(p.contains(7));
+        assert!(!p.contains(8));
+        p.extract_min();
+        assert!(!p.contains(7));
+    }
 }
@@ -0,0 +1,450 @@
+use crate::{algo::avoid::segment_in_polygon, ds::graph::Graph, utils::coordinate::Coordinate};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    f64::consts::PI,
+};
+
+// children per node
+const NODE_CAPACITY: usize = 16;
+const METRES_PER_DEGREE: f64 = 6371000.0 * PI / 180.0;
+
+// a straight piece of road between two nodes, the ids of the coordinates are the node ids
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub from: Coordinate,
+    pub to: Coordinate,
+}
+
+impl Segment {
+    pub fn bbox(&self) -> BBox {
+        BBox::of(&self.from, &self.to)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BBox {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+impl BBox {
+    pub fn of(a: &Coordinate, b: &Coordinate) -> Self {
+        Self {
+            min_lat: a.lat.min(b.lat),
+            min_lng: a.lng.min(b.lng),
+            max_lat: a.lat.max(b.lat),
+            max_lng: a.lng.max(b.lng),
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min_lat: self.min_lat.min(other.min_lat),
+            min_lng: self.min_lng.min(other.min_lng),
+            max_lat: self.max_lat.max(other.max_lat),
+            max_lng: self.max_lng.max(other.max_lng),
+        }
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min_lat <= other.max_lat
+            && other.min_lat <= self.max_lat
+            && self.min_lng <= other.max_lng
+            && other.min_lng <= self.max_lng
+    }
+
+    pub fn contains(&self, point: &Coordinate) -> bool {
+        (self.min_lat..=self.max_lat).contains(&point.lat) && (self.min_lng..=self.max_lng).contains(&point.lng)
+    }
+
+    fn center(&self) -> (f64, f64) {
+        ((self.min_lat + self.max_lat) / 2.0, (self.min_lng + self.max_lng) / 2.0)
+    }
+
+    fn corners(&self) -> [Coordinate; 4] {
+        let corner = |lat, lng| Coordinate { lat, lng, id: 0 };
+        [
+            corner(self.min_lat, self.min_lng),
+            corner(self.min_lat, self.max_lng),
+            corner(self.max_lat, self.max_lng),
+            corner(self.max_lat, self.min_lng),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NearestSegment<'a> {
+    pub segment: &'a Segment,
+    // how far along from `from` to `to` the closest point is, between 0 and 1
+    pub fraction: f64,
+    pub distance: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bbox: BBox,
+    // children in the level below, or segments for the lowest level
+    start: usize,
+    end: usize,
+}
+
+// R-tree over road segments, bulk loaded with Sort-Tile-Recursive packing.
+// levels[0] holds the leaves and the last level the root.
+#[derive(Debug, Clone, Default)]
+pub struct RTree {
+    segments: Vec<Segment>,
+    levels: Vec<Vec<Node>>,
+}
+
+impl RTree {
+    pub fn new(mut segments: Vec<Segment>) -> Self {
+        let mut levels = vec![];
+        if !segments.is_empty() {
+            let mut level = pack(&mut segments, Segment::bbox);
+            while level.len() > 1 {
+                let parent = pack(&mut level, |node: &Node| node.bbox);
+                levels.push(level);
+                level = parent;
+            }
+            levels.push(level);
+        }
+        Self { segments, levels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    // Closest allowed segment to point. Distances are measured on a flat
+    // projection centred on point, so node boxes give exact lower bounds and
+    // the first segment out of the queue is the nearest.
+    pub fn nearest_segment(&self, point: &Coordinate, allowed: &dyn Fn(&Segment) -> bool) -> Option<NearestSegment<'_>> {
+        let project = |c: &Coordinate| {
+            (
+                (c.lng - point.lng) * point.lat.to_radians().cos() * METRES_PER_DEGREE,
+                (c.lat - point.lat) * METRES_PER_DEGREE,
+            )
+        };
+        let box_distance = |bbox: &BBox| {
+            let (min_x, min_y) = project(&Coordinate { lat: bbox.min_lat, lng: bbox.min_lng, id: 0 });
+            let (max_x, max_y) = project(&Coordinate { lat: bbox.max_lat, lng: bbox.max_lng, id: 0 });
+            min_x.max(-max_x).max(0.0).hypot(min_y.max(-max_y).max(0.0))
+        };
+
+        let mut queue = BinaryHeap::new();
+        if let Some(root) = self.levels.last() {
+            for (index, node) in root.iter().enumerate() {
+                queue.push(Candidate { distance: box_distance(&node.bbox), level: Some(self.levels.len() - 1), index, fraction: 0.0 });
+            }
+        }
+        while let Some(candidate) = queue.pop() {
+            let Some(level) = candidate.level else {
+                return Some(NearestSegment {
+                    segment: &self.segments[candidate.index],
+                    fraction: candidate.fraction,
+                    distance: candidate.distance,
+                });
+            };
+            let node = &self.levels[level][candidate.index];
+            for index in node.start..node.end {
+                if level > 0 {
+                    let child = &self.levels[level - 1][index];
+                    queue.push(Candidate { distance: box_distance(&child.bbox), level: Some(level - 1), index, fraction: 0.0 });
+                    continue;
+                }
+                let segment = &self.segments[index];
+                if !allowed(segment) {
+                    continue;
+                }
+                let (ax, ay) = project(&segment.from);
+                let (bx, by) = project(&segment.to);
+                let (dx, dy) = (bx - ax, by - ay);
+                let length = dx * dx + dy * dy;
+                let fraction = if length == 0.0 {
+                    0.0
+                } else {
+                    (-(ax * dx + ay * dy) / length).clamp(0.0, 1.0)
+                };
+                let distance = (ax + fraction * dx).hypot(ay + fraction * dy);
+                queue.push(Candidate { distance, level: None, index, fraction });
+            }
+        }
+        None
+    }
+
+    // segments with some part inside the window
+    pub fn window(&self, window: &BBox) -> Vec<&Segment> {
+        self.candidates(window)
+            .into_iter()
+            .filter(|segment| {
+                let corners = window.corners();
+                window.contains(&segment.from)
+                    || window.contains(&segment.to)
+                    || (0..4).any(|i| segments_intersect(&segment.from, &segment.to, &corners[i], &corners[(i + 1) % 4]))
+            })
+            .collect()
+    }
+
+    // segments with some part inside the polygon, which may be open or closed
+    pub fn intersecting_polygon(&self, polygon: &[Coordinate]) -> Vec<&Segment> {
+        let Some(bbox) = polygon.iter().map(|c| BBox::of(c, c)).reduce(BBox::union) else {
+            return vec![];
+        };
+        self.candidates(&bbox)
+            .into_iter()
+            .filter(|segment| segment_in_polygon(&segment.from, &segment.to, polygon))
+            .collect()
+    }
+
+    // segments whose bounding box intersects bbox
+    fn candidates(&self, bbox: &BBox) -> Vec<&Segment> {
+        let mut found = vec![];
+        let Some(root) = self.levels.len().checked_sub(1) else {
+            return found;
+        };
+        let mut stack: Vec<(usize, usize)> = (0..self.levels[root].len()).map(|index| (root, index)).collect();
+        while let Some((level, index)) = stack.pop() {
+            let node = &self.levels[level][index];
+            if !node.bbox.intersects(bbox) {
+                continue;
+            }
+            if level == 0 {
+                found.extend(self.segments[node.start..node.end].iter().filter(|segment| segment.bbox().intersects(bbox)));
+            } else {
+                stack.extend((node.start..node.end).map(|child| (level - 1, child)));
+            }
+        }
+        found
+    }
+}
+
+// Sort-Tile-Recursive: sorts the items into vertical slabs by longitude, each
+// slab by latitude, and cuts them into runs of NODE_CAPACITY that become the
+// nodes of the level above
+fn pack<E>(items: &mut [E], bbox: impl Fn(&E) -> BBox) -> Vec<Node> {
+    let nodes = items.len().div_ceil(NODE_CAPACITY);
+    let slabs = (nodes as f64).sqrt().ceil() as usize;
+    items.sort_by(|a, b| bbox(a).center().1.total_cmp(&bbox(b).center().1));
+    for slab in items.chunks_mut(slabs * NODE_CAPACITY) {
+        slab.sort_by(|a, b| bbox(a).center().0.total_cmp(&bbox(b).center().0));
+    }
+    (0..items.len())
+        .step_by(NODE_CAPACITY)
+        .map(|start| {
+            let end = (start + NODE_CAPACITY).min(items.len());
+            let bbox = items[start..end].iter().map(&bbox).reduce(BBox::union).unwrap();
+            Node { bbox, start, end }
+        })
+        .collect()
+}
+
+// whether the segments p1-p2 and q1-q2 touch, with lng as x and lat as y
+pub fn segments_intersect(p1: &Coordinate, p2: &Coordinate, q1: &Coordinate, q2: &Coordinate) -> bool {
+    let orientation = |a: &Coordinate, b: &Coordinate, c: &Coordinate| {
+        let cross = (b.lng - a.lng) * (c.lat - a.lat) - (b.lat - a.lat) * (c.lng - a.lng);
+        (cross > 0.0) as i8 - (cross < 0.0) as i8
+    };
+    // c is known to be on the line a-b
+    let on_segment = |a: &Coordinate, b: &Coordinate, c: &Coordinate| BBox::of(a, b).contains(c);
+    let (d1, d2) = (orientation(q1, q2, p1), orientation(q1, q2, p2));
+    let (d3, d4) = (orientation(p1, p2, q1), orientation(p1, p2, q2));
+    if d1 * d2 < 0 && d3 * d4 < 0 {
+        return true;
+    }
+    (d1 == 0 && on_segment(q1, q2, p1))
+        || (d2 == 0 && on_segment(q1, q2, p2))
+        || (d3 == 0 && on_segment(p1, p2, q1))
+        || (d4 == 0 && on_segment(p1, p2, q2))
+}
+
+// one segment per arc, so a two-way road has a segment in each direction
+pub fn graph_segments(g: &Graph, map: &HashMap<usize, Coordinate>) -> Vec<Segment> {
+    let mut seen = HashSet::new();
+    let mut segments = vec![];
+    for (u, edges) in g.edges.iter().enumerate() {
+        for &v in edges {
+            if u == v || !seen.insert((u, v)) {
+                continue;
+            }
+            if let (Some(&from), Some(&to)) = (map.get(&u), map.get(&v)) {
+                segments.push(Segment { from, to });
+            }
+        }
+    }
+    segments
+}
+
+// entry of the nearest segment search, closest first
+struct Candidate {
+    distance: f64,
+    // level of the node, None for a segment
+    level: Option<usize>,
+    index: usize,
+    fraction: f64,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::avoid::point_in_polygon;
+    use rand::Rng;
+
+    fn coordinate(lat: f64, lng: f64) -> Coordinate {
+        Coordinate { lat, lng, id: 0 }
+    }
+
+    // short random segments around Bogotá
+    fn random_segments(n: usize) -> Vec<Segment> {
+        let mut rng = rand::thread_rng();
+        (0..n)
+            .map(|i| {
+                let (lat, lng) = (rng.gen_range(4.5..4.8), rng.gen_range(-74.2..-74.0));
+                let from = Coordinate { lat, lng, id: 2 * i };
+                let to = Coordinate { lat: lat + rng.gen_range(-0.005..0.005), lng: lng + rng.gen_range(-0.005..0.005), id: 2 * i + 1 };
+                Segment { from, to }
+            })
+            .collect()
+    }
+
+    fn ids(segments: Vec<&Segment>) -> Vec<usize> {
+        let mut ids: Vec<usize> = segments.iter().map(|segment| segment.from.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_nearest_segment_matches_brute_force() {
+        let mut rng = rand::thread_rng();
+        let segments = random_segments(2000);
+        let tree = RTree::new(segments.clone());
+        assert_eq!(tree.len(), 2000);
+        assert!(tree.levels.len() > 2);
+        let even = |segment: &Segment| segment.from.id.is_multiple_of(4);
+        for _ in 0..50 {
+            let point = coordinate(rng.gen_range(4.4..4.9), rng.gen_range(-74.3..-73.9));
+            let nearest = tree.nearest_segment(&point, &even).unwrap();
+            assert!(even(nearest.segment));
+            let tree_alone = RTree::new(vec![*nearest.segment]);
+            let expected = segments
+                .iter()
+                .filter(|segment| even(segment))
+                .map(|segment| RTree::new(vec![*segment]).nearest_segment(&point, &|_| true).unwrap().distance)
+                .min_by(|a, b| a.total_cmp(b))
+                .unwrap();
+            assert_eq!(nearest.distance, expected);
+            assert_eq!(tree_alone.nearest_segment(&point, &|_| true).unwrap(), nearest);
+        }
+        assert!(tree.nearest_segment(&point_far_away(), &|_| false).is_none());
+        assert!(RTree::default().nearest_segment(&point_far_away(), &|_| true).is_none());
+    }
+
+    fn point_far_away() -> Coordinate {
+        coordinate(-30.0, 20.0)
+    }
+
+    #[test]
+    fn test_nearest_segment_fraction() {
+        let from = Coordinate { lat: 0.0, lng: 0.0, id: 0 };
+        let to = Coordinate { lat: 0.0, lng: 0.01, id: 1 };
+        let tree = RTree::new(vec![Segment { from, to }]);
+        let nearest = tree.nearest_segment(&coordinate(0.001, 0.0025), &|_| true).unwrap();
+        assert!((nearest.fraction - 0.25).abs() < 1e-9);
+        assert!((nearest.distance - 111.19).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_window_and_polygon_match_brute_force() {
+        let mut rng = rand::thread_rng();
+        let segments = random_segments(2000);
+        let tree = RTree::new(segments.clone());
+        for _ in 0..20 {
+            let (lat, lng) = (rng.gen_range(4.5..4.8), rng.gen_range(-74.2..-74.0));
+            let window = BBox { min_lat: lat, min_lng: lng, max_lat: lat + 0.02, max_lng: lng + 0.03 };
+            let expected: Vec<&Segment> = segments
+                .iter()
+                .filter(|segment| {
+                    // sample points along the segment
+                    (0..=100).any(|i| {
+                        let t = i as f64 / 100.0;
+                        window.contains(&coordinate(
+                            segment.from.lat + t * (segment.to.lat - segment.from.lat),
+                            segment.from.lng + t * (segment.to.lng - segment.from.lng),
+                        ))
+                    })
+                })
+                .collect();
+            let found = ids(tree.window(&window));
+            // sampling can miss a segment that only clips a corner
+            assert!(ids(expected).iter().all(|id| found.binary_search(id).is_ok()));
+
+            // the window as a polygon, and a triangle in it
+            assert_eq!(ids(tree.intersecting_polygon(&window.corners())), found);
+            let triangle = [coordinate(lat, lng), coordinate(lat, lng + 0.03), coordinate(lat + 0.02, lng)];
+            let in_triangle = ids(tree.intersecting_polygon(&triangle));
+            let expected: Vec<&Segment> = segments
+                .iter()
+                .filter(|segment| {
+                    point_in_polygon(&segment.from, &triangle)
+                        || point_in_polygon(&segment.to, &triangle)
+                        || (0..3).any(|i| segments_intersect(&segment.from, &segment.to, &triangle[i], &triangle[(i + 1) % 3]))
+                })
+                .collect();
+            assert_eq!(in_triangle, ids(expected));
+            assert!(in_triangle.iter().all(|id| found.binary_search(id).is_ok()));
+        }
+        assert!(tree.intersecting_polygon(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_segments_intersect() {
+        let (a, b) = (coordinate(0.0, 0.0), coordinate(2.0, 2.0));
+        assert!(segments_intersect(&a, &b, &coordinate(0.0, 2.0), &coordinate(2.0, 0.0)));
+        assert!(segments_intersect(&a, &b, &coordinate(1.0, 1.0), &coordinate(5.0, 0.0)));
+        assert!(!segments_intersect(&a, &b, &coordinate(0.0, 1.0), &coordinate(1.0, 2.0)));
+        assert!(!segments_intersect(&a, &b, &coordinate(3.0, 3.0), &coordinate(4.0, 4.0)));
+    }
+
+    #[test]
+    fn test_graph_segments() {
+        let mut map = HashMap::new();
+        for i in 0..3 {
+            map.insert(i, Coordinate { lat: 0.0, lng: i as f64, id: i });
+        }
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(1, 0, 1.0);
+        g.add_edge(1, 2, 1.0);
+        g.add_edge(1, 2, 2.0);
+        g.add_edge(2, 2, 1.0);
+        let segments = graph_segments(&g, &map);
+        let pairs: Vec<(usize, usize)> = segments.iter().map(|s| (s.from.id, s.to.id)).collect();
+        assert_eq!(pairs, vec![(0, 1), (1, 0), (1, 2)]);
+    }
+}
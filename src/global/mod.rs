@@ -1,8 +1,48 @@
-use crate::{ds::{graph::Graph, kdtree::KdTree}, utils::coordinate::Coordinate};
-use std::collections::HashMap;
+use crate::{algo::{contraction_hierarchies::ContractionHierarchy, elevation::Elevations, landmarks::Landmarks, maneuvers::StreetNames, scc::Components, search_workspace::WorkspacePool, time_dependent::TimeDependentGraph, traffic::WeightOverlay, turns::TurnGraph}, ds::{graph::Graph, kdtree::KdTree, rtree::RTree}, utils::{coordinate::Coordinate, profile::RoutingProfile}};
+use std::{collections::HashMap, sync::{Arc, RwLock}};
 
-pub struct Data {
+// Arcs of one routing profile and everything precomputed from them. All
+// profiles share the node ids of the coordinates file.
+pub struct Network {
     pub graph: Graph,
+    pub reverse_graph: Graph,
+    pub contraction_hierarchy: Option<ContractionHierarchy>,
+    pub landmarks: Option<Landmarks>,
+    pub turn_graph: Option<TurnGraph>,
+    pub components: Components,
+    // one segment per arc, for snapping to roads and finding the arcs in an area
+    pub segment_tree: RTree,
+}
+
+pub struct Data {
+    pub networks: HashMap<RoutingProfile, Network>,
     pub map_id_to_coordinates: HashMap<usize, Coordinate>,
-    pub kd_tree: KdTree<f64>,
+    pub kd_tree: KdTree<usize>,
+    // travel times and traffic refer to the car network
+    pub travel_times: Option<TimeDependentGraph>,
+    pub traffic: RwLock<Arc<WeightOverlay>>,
+    pub workspaces: WorkspacePool,
+    // empty when no street names file is configured
+    pub street_names: StreetNames,
+    pub elevations: Option<Elevations>,
+}
+
+impl Data {
+    pub fn network(&self, profile: RoutingProfile) -> Option<&Network> {
+        self.networks.get(&profile)
+    }
+
+    // the car network is always loaded
+    pub fn car_network(&self) -> &Network {
+        &self.networks[&RoutingProfile::Car]
+    }
+
+    // requests work on a snapshot, so an update never changes weights mid-request
+    pub fn traffic(&self) -> Arc<WeightOverlay> {
+        self.traffic.read().unwrap().clone()
+    }
+
+    pub fn set_traffic(&self, overlay: WeightOverlay) {
+        *self.traffic.write().unwrap() = Arc::new(overlay);
+    }
 }
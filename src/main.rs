@@ -7,13 +7,20 @@ use rocket_cors::{AllowedOrigins, CorsOptions};
 use std::env;
 use dotenvy::dotenv;
 use std::path::{Path, PathBuf};
-use tsp::routes::{login::login,
-    shortestpath::shortestpath,
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tsp::routes::{alternatives::alternatives,
+    login::login,
+    map_matching::map_match,
+    matrix::matrix,
+    shortestpath::{route, shortestpath},
     signup::sign_up,
+    traffic::{clear_traffic, upload_traffic},
     history::get_history,
+    isochrone::isochrone,
     user::get_user_details,
 };
-use tsp::{global::Data, utils};
+use tsp::{algo::{maneuvers::StreetNames, scc::strongly_connected_components, search_workspace::WorkspacePool, traffic::WeightOverlay}, ds::rtree::{graph_segments, RTree}, global::{Data, Network}, utils::{self, coordinate::Coordinate, profile::RoutingProfile}};
 
 #[get("/")]
 async fn index() -> Option<NamedFile> {
@@ -25,22 +32,86 @@ async fn files(file: PathBuf) -> Option<NamedFile> {
     NamedFile::open(Path::new("static/").join(file)).await.ok()
 }
 
+// loads the arcs of a profile and whatever its environment variables ask to
+// precompute, None when the profile has no arcs file
+fn load_network(profile: RoutingProfile, coordinates_file: &String, map: &HashMap<usize, Coordinate>) -> Option<Network> {
+    let var = |name: &str| env::var(format!("{}{}", profile.env_prefix(), name)).ok();
+    let arcs_file = var("ARCS_FILE")?;
+
+    let graph = utils::create_adjacency_list_from_files(coordinates_file, &arcs_file).unwrap();
+    let reverse_graph = graph.reverse();
+    let contraction_hierarchy = var("CH_FILE").map(|hierarchy_file| {
+        utils::create_contraction_hierarchy_from_file(&hierarchy_file, &graph).unwrap()
+    });
+    let landmarks = var("LANDMARKS_FILE").map(|landmarks_file| {
+        utils::create_landmarks_from_file(&landmarks_file, &graph).unwrap()
+    });
+    let turn_graph = var("TURN_RESTRICTIONS_FILE").map(|restrictions_file| {
+        utils::create_turn_graph_from_file(&restrictions_file, &graph, var("U_TURN_PENALTY").as_deref()).unwrap()
+    });
+
+    let components = strongly_connected_components(&graph);
+    let segment_tree = RTree::new(graph_segments(&graph, map));
+
+    Some(Network {
+        graph,
+        reverse_graph,
+        contraction_hierarchy,
+        landmarks,
+        turn_graph,
+        components,
+        segment_tree,
+    })
+}
+
 #[launch]
 fn rocket() -> _ {
     dotenv().ok();
     println!("Starting server...");
     let coordinates_file = env::var("COORDINATES_FILE").unwrap();
-    let arcs_file = env::var("ARCS_FILE").unwrap();
-
-    let graph = utils::create_adjacency_list_from_files(&coordinates_file, &arcs_file).unwrap();
     let map_id_to_coordinates =
         utils::create_id_to_coordinates_hashmap_from_file(&coordinates_file).unwrap();
+
+    let mut networks = HashMap::new();
+    for profile in RoutingProfile::ALL {
+        if let Some(network) = load_network(profile, &coordinates_file, &map_id_to_coordinates) {
+            networks.insert(profile, network);
+        }
+    }
+    let car_graph = &networks.get(&RoutingProfile::Car).expect("ARCS_FILE must be set").graph;
+
     let kd_tree = utils::create_kd_tree_from_file(&coordinates_file).unwrap();
+    let elevations = env::var("ELEVATION_FILE").ok().map(|elevation_file| {
+        utils::create_elevations_from_file(&elevation_file).unwrap()
+    });
+
+    let travel_times = env::var("TRAVEL_TIMES_FILE").ok().map(|profiles_file| {
+        let speed = env::var("FREE_FLOW_SPEED")
+            .map(|speed| speed.parse().unwrap())
+            .unwrap_or(13.9);
+        utils::create_time_dependent_graph_from_file(&profiles_file, speed).unwrap()
+    });
+    let traffic = match env::var("TRAFFIC_FILE") {
+        Ok(traffic_file) => utils::create_weight_overlay_from_file(&traffic_file, car_graph).unwrap(),
+        Err(_) => WeightOverlay::default(),
+    };
+
+    let street_names = match env::var("STREET_NAMES_FILE") {
+        Ok(names_file) => utils::create_street_names_from_file(&names_file).unwrap(),
+        Err(_) => StreetNames::default(),
+    };
+
+    let workspaces = WorkspacePool::new(car_graph.edges.len());
 
     let state = Data {
-        graph,
+        networks,
         map_id_to_coordinates,
         kd_tree,
+        travel_times,
+        traffic: RwLock::new(Arc::new(traffic)),
+        workspaces,
+        street_names,
+        elevations,
     };
     println!("Nodes in graph");
 
@@ -49,7 +120,7 @@ fn rocket() -> _ {
     let cors = CorsOptions::default()
         .allowed_origins(allowed_origins)
         .allowed_methods(
-            vec![Method::Get, Method::Post, Method::Patch]
+            vec![Method::Get, Method::Post, Method::Patch, Method::Delete]
                 .into_iter()
                 .map(From::from)
                 .collect(),
@@ -58,7 +129,7 @@ fn rocket() -> _ {
     println!("Rocket ready to launch");
     rocket::build()
         .manage(state)
-        .mount("/", routes![shortestpath, index, files])
+        .mount("/", routes![shortestpath, route, matrix, map_match, alternatives, isochrone, index, files])
         .mount("/map", routes![index])
         .mount("/history", routes![get_history, index])
         .mount("/new-trip", routes![index])
@@ -66,5 +137,6 @@ fn rocket() -> _ {
         .mount("/login", routes![login, index])
         .mount("/account", routes![index])
         .mount("/user", routes![get_user_details])
+        .mount("/traffic", routes![upload_traffic, clear_traffic])
         .attach(cors.to_cors().unwrap())
 }
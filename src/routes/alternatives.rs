@@ -0,0 +1,124 @@
+use crate::{
+    algo::{
+        avoid::Avoid,
+        error::RoutingError,
+        shortest_paths::{alternative_routes, alternative_routes_between, path_cost, AlternativeOptions},
+    },
+    global::Data,
+    routes::shortestpath::{snap_coordinate, validate_coordinates},
+    utils::{
+        auth_token::Token,
+        authenticate::authenticate,
+        coordinate::Coordinate,
+        profile::RoutingProfile,
+        response::{DataResponse, ErrorResponse},
+    },
+};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{http::Status, post, response::status::Custom, serde::json::Json, State};
+
+// every alternative costs a handful of penalised searches
+const MAX_ALTERNATIVES: usize = 5;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AlternativesRequest {
+    pub from: Coordinate,
+    pub to: Coordinate,
+    #[serde(default = "default_alternatives")]
+    pub alternatives: usize,
+    #[serde(default)]
+    pub profile: RoutingProfile,
+}
+
+fn default_alternatives() -> usize {
+    2
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AlternativeRoute {
+    pub path: Vec<Coordinate>,
+    // metres driven
+    pub distance: f64,
+    // what the routes are compared by: the distance plus traffic delays
+    pub cost: f64,
+}
+
+// first route is the shortest path, the rest are alternatives ordered as found
+#[post("/alternatives", data = "<data>")]
+pub fn alternatives(
+    token_raw: Token,
+    data: Json<AlternativesRequest>,
+    state: &State<Data>,
+) -> Result<Json<DataResponse<Vec<AlternativeRoute>>>, Custom<Json<ErrorResponse>>> {
+    let token_raw = token_raw.bearer();
+    if !authenticate(token_raw) {
+        let response = ErrorResponse {
+            message: "Invalid session token".to_string(),
+            code: None,
+        };
+        return Err(Custom(Status::Unauthorized, Json(response)));
+    }
+
+    if data.alternatives > MAX_ALTERNATIVES {
+        return Err(RoutingError::TooManyAlternatives { alternatives: data.alternatives, max: MAX_ALTERNATIVES }.into());
+    }
+    validate_coordinates([&data.from, &data.to])?;
+    let Some(network) = state.network(data.profile) else {
+        return Err(RoutingError::ProfileUnavailable(data.profile).into());
+    };
+    let options = AlternativeOptions {
+        max_alternatives: data.alternatives,
+        ..Default::default()
+    };
+    let avoid = Avoid::default();
+    let start_approximation = snap_coordinate(state, network, &data.from, &avoid);
+    let end_approximation = snap_coordinate(state, network, &data.to, &avoid);
+    // traffic overlays only describe the car network
+    let traffic = state.traffic();
+    let edge_cost = |u: usize, v: usize, w: f64| match data.profile {
+        RoutingProfile::Car => Some(traffic.weight(u, v, w)),
+        _ => Some(w),
+    };
+    let mut workspace = state.workspaces.get();
+    for &src in start_approximation.iter() {
+        for &dest in end_approximation.iter() {
+            if !network.components.reaches(src, dest) {
+                continue;
+            }
+            // with turn restrictions the routes run over the arcs of the turn
+            // graph, leaving src on any arc and reaching dest on any arc
+            let found = match &network.turn_graph {
+                Some(turn_graph) => {
+                    let sources = turn_graph.arcs_from(src, &edge_cost);
+                    let targets: Vec<(usize, f64)> = turn_graph
+                        .arcs_into(dest, &network.reverse_graph)
+                        .into_iter()
+                        .map(|arc| (arc, 0.0))
+                        .collect();
+                    workspace.fit(turn_graph.graph.edges.len());
+                    alternative_routes_between(&mut workspace, &turn_graph.graph, &sources, &targets, &options, &turn_graph.turn_edge_cost(&edge_cost))
+                        .map(|routes| routes.into_iter().map(|(cost, arcs)| (cost, turn_graph.nodes(&arcs))).collect())
+                }
+                None => alternative_routes(&mut workspace, &network.graph, src, dest, &options, &edge_cost),
+            };
+            if let Ok(found) = found {
+                let routes = found
+                    .into_iter()
+                    .map(|(cost, nodes)| AlternativeRoute {
+                        path: nodes
+                            .iter()
+                            .map(|node| state.map_id_to_coordinates[node])
+                            .collect(),
+                        distance: path_cost(&network.graph, &nodes),
+                        cost,
+                    })
+                    .collect();
+                return Ok(Json(DataResponse { data: routes }));
+            }
+        }
+    }
+
+    Err(RoutingError::UnreachableLeg { from: 0, to: 1 }.into())
+}
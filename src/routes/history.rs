@@ -1,18 +1,19 @@
 use rocket::http::Status;
 use rocket::{get, serde::json::Json, response::status::Custom};
 use crate::db::models::trips::Trip;
-use crate::{utils::{response::{DataResponse, ErrorResponse}, auth_token::Token, authenticate::{authenticate, get_claims_by_token}}, db::{users::get_user_by_id, trips::get_trips_by_user_id}};
+use crate::{utils::{response::{DataResponse, ErrorResponse}, auth_token::Token, authenticate::{authenticate, get_claims_by_token}, path::Path}, db::{users::get_user_by_id, trips::get_trips_by_user_id}};
 
 
 #[get("/<page>")]
 pub fn get_history(page: i64, token_raw: Token) -> Result< Json<DataResponse<Vec<Trip>>>, Custom<Json<ErrorResponse>>> {
-    let token_raw = token_raw.tkn.split(' ').collect::<Vec<&str>>()[1];
+    let token_raw = token_raw.bearer();
     if authenticate(token_raw) {
         let token_claims = get_claims_by_token(token_raw).unwrap();
         let user = get_user_by_id(&token_claims.uid).unwrap();
 
         if user.username == token_claims.username {
-            let trips = get_trips_by_user_id(&user.id, page).unwrap();
+            let mut trips = get_trips_by_user_id(&user.id, page).unwrap();
+            trips.iter_mut().for_each(decode_path);
 
             let response = DataResponse {
                 data: trips,
@@ -22,6 +23,17 @@ pub fn get_history(page: i64, token_raw: Token) -> Result< Json<DataResponse<Vec
     }
     let response = ErrorResponse {
         message: "Invalid Token".to_string(),
+        code: None,
     };
     Err(Custom(Status::Unauthorized, Json(response)))
-}
\ No newline at end of file
+}
+
+// trips are stored with their geometry encoded, readers get the points back
+fn decode_path(trip: &mut Trip) {
+    let Ok(mut path) = serde_json::from_value::<Path>(trip.path.clone()) else {
+        return;
+    };
+    if path.decode().is_ok() {
+        trip.path = serde_json::to_value(path).unwrap();
+    }
+}
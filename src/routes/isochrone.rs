@@ -0,0 +1,170 @@
+use crate::{
+    algo::{avoid::Avoid, error::RoutingError, hull::convex_hull, shortest_paths::bounded_dijkstra, turns::turn_aware_bounded_dijkstra},
+    global::Data,
+    routes::shortestpath::{snap_coordinate, validate_coordinates},
+    utils::{
+        auth_token::Token, authenticate::authenticate, coordinate::Coordinate,
+        profile::RoutingProfile, response::ErrorResponse,
+    },
+};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{http::Status, post, response::status::Custom, serde::json::Json, State};
+use serde_json::{json, Value};
+
+// larger budgets settle a good part of the network on every request
+const MAX_TIME: f64 = 3600.0;
+const MAX_DISTANCE: f64 = 50000.0;
+
+// how far the isochrone reaches, e.g. {"time": 900} for 15 minutes or
+// {"distance": 10000} for 10 km
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Budget {
+    // seconds, travelled at the speed of the profile
+    Time(f64),
+    // metres along the roads
+    Distance(f64),
+}
+
+impl Budget {
+    // budget in metres, the unit of the arc weights
+    fn max_distance(&self, profile: RoutingProfile) -> Result<f64, RoutingError> {
+        let (value, max, distance) = match *self {
+            Budget::Time(seconds) => (seconds, MAX_TIME, seconds * profile.speed()),
+            Budget::Distance(metres) => (metres, MAX_DISTANCE, metres),
+        };
+        if !value.is_finite() || value <= 0.0 || value > max {
+            return Err(RoutingError::InvalidBudget { max_time: MAX_TIME, max_distance: MAX_DISTANCE });
+        }
+        Ok(distance)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IsochroneRequest {
+    pub origin: Coordinate,
+    pub budget: Budget,
+    #[serde(default)]
+    pub profile: RoutingProfile,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ReachableNode {
+    pub coordinate: Coordinate,
+    // metres from the origin, stretched by traffic for time budgets
+    pub cost: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Isochrone {
+    pub origin: Coordinate,
+    pub nodes: Vec<ReachableNode>,
+    // GeoJSON Feature with the convex hull of the reachable nodes
+    pub outline: Value,
+}
+
+#[post("/isochrone", data = "<data>")]
+pub fn isochrone(
+    token_raw: Token,
+    data: Json<IsochroneRequest>,
+    state: &State<Data>,
+) -> Result<Json<Isochrone>, Custom<Json<ErrorResponse>>> {
+    let token_raw = token_raw.bearer();
+    if !authenticate(token_raw) {
+        let response = ErrorResponse {
+            message: "Invalid session token".to_string(),
+            code: None,
+        };
+        return Err(Custom(Status::Unauthorized, Json(response)));
+    }
+
+    let max_distance = data.budget.max_distance(data.profile)?;
+    validate_coordinates([&data.origin])?;
+    let Some(network) = state.network(data.profile) else {
+        return Err(RoutingError::ProfileUnavailable(data.profile).into());
+    };
+
+    // nearest snapped node that can actually leave its position
+    let candidates = snap_coordinate(state, network, &data.origin, &Avoid::default());
+    let origin = candidates
+        .into_iter()
+        .find(|&node| !network.graph.edges[node].is_empty());
+    let Some(origin) = origin else {
+        return Err(RoutingError::SnapFailed { stop: 0 }.into());
+    };
+
+    // traffic slows cars down but leaves road lengths alone, and overlays
+    // only describe the car network
+    let traffic = state.traffic();
+    let edge_cost = |u: usize, v: usize, w: f64| match (data.profile, data.budget) {
+        (RoutingProfile::Car, Budget::Time(_)) => Some(traffic.weight(u, v, w)),
+        _ => Some(w),
+    };
+    let reached = match &network.turn_graph {
+        Some(turn_graph) => turn_aware_bounded_dijkstra(&mut state.workspaces.get(), turn_graph, origin, max_distance, &edge_cost),
+        None => bounded_dijkstra(&network.graph, origin, max_distance, &edge_cost),
+    };
+    let nodes: Vec<ReachableNode> = reached
+        .into_iter()
+        .map(|(node, cost)| ReachableNode {
+            coordinate: state.map_id_to_coordinates[&node],
+            cost,
+        })
+        .collect();
+
+    Ok(Json(Isochrone {
+        origin: state.map_id_to_coordinates[&origin],
+        outline: outline_feature(&nodes, data.budget),
+        nodes,
+    }))
+}
+
+fn outline_feature(nodes: &[ReachableNode], budget: Budget) -> Value {
+    let points: Vec<(f64, f64)> = nodes
+        .iter()
+        .map(|node| (node.coordinate.lng, node.coordinate.lat))
+        .collect();
+    let mut ring: Vec<[f64; 2]> = convex_hull(&points)
+        .into_iter()
+        .map(|(lng, lat)| [lng, lat])
+        .collect();
+    // GeoJSON rings are closed and need at least four positions
+    while !ring.is_empty() && ring.len() < 3 {
+        ring.push(ring[ring.len() - 1]);
+    }
+    if let Some(&first) = ring.first() {
+        ring.push(first);
+    }
+    json!({
+        "type": "Feature",
+        "properties": { "budget": budget },
+        "geometry": {
+            "type": "Polygon",
+            "coordinates": [ring],
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let budget: Budget = serde_json::from_str(r#"{"time": 900}"#).unwrap();
+        assert_eq!(budget, Budget::Time(900.0));
+        assert_eq!(budget.max_distance(RoutingProfile::Walking), Ok(900.0 * 1.4));
+        let budget: Budget = serde_json::from_str(r#"{"distance": 10000}"#).unwrap();
+        assert_eq!(budget.max_distance(RoutingProfile::Car), Ok(10000.0));
+
+        assert!(Budget::Time(MAX_TIME + 1.0).max_distance(RoutingProfile::Car).is_err());
+        assert!(Budget::Distance(-1.0).max_distance(RoutingProfile::Car).is_err());
+        assert!(Budget::Distance(f64::NAN).max_distance(RoutingProfile::Car).is_err());
+        let error = Budget::Time(0.0).max_distance(RoutingProfile::Car).unwrap_err();
+        assert_eq!(error.code(), "invalid_budget");
+        assert!(serde_json::from_str::<Budget>(r#"{"cost": 10}"#).is_err());
+    }
+}
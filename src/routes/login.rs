@@ -22,7 +22,8 @@ pub fn login(body: Json<Body<'_>>) -> Result<Json<OkResponse>, Custom<Json<Error
         Ok(user) => {
             if user.len() == 0 {
                 let response: ErrorResponse = ErrorResponse {
-                    message: "authentication failed".to_string()
+                    message: "authentication failed".to_string(),
+                    code: None,
                 };
                 return Err(Custom(Status::Unauthorized, Json(response)));
             }
@@ -50,14 +51,16 @@ pub fn login(body: Json<Body<'_>>) -> Result<Json<OkResponse>, Custom<Json<Error
                 Ok(Json(response))
             } else {
                 let response: ErrorResponse = ErrorResponse {
-                    message: "authentication failed".to_string()
+                    message: "authentication failed".to_string(),
+                    code: None,
                 };
                 Err(Custom(Status::Unauthorized, Json(response)))
             }
         },
         Err(error) => {
             let response: ErrorResponse = ErrorResponse {
-                message: format!("error: {:?}", error)
+                message: format!("error: {:?}", error),
+                code: None,
             };
             Err(Custom(Status::Unauthorized, Json(response)))
         },
@@ -0,0 +1,88 @@
+use crate::{
+    algo::{
+        avoid::Avoid,
+        error::RoutingError,
+        map_matching::{match_trace, MatchOptions},
+    },
+    global::Data,
+    routes::shortestpath::{snap_coordinate, validate_coordinates},
+    utils::{
+        auth_token::Token,
+        authenticate::authenticate,
+        coordinate::Coordinate,
+        profile::RoutingProfile,
+        response::{DataResponse, ErrorResponse},
+    },
+};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{http::Status, post, response::status::Custom, serde::json::Json, State};
+
+const MAX_TRACE_POINTS: usize = 2000;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MatchRequest {
+    pub trace: Vec<Coordinate>,
+    #[serde(default)]
+    pub profile: RoutingProfile,
+    // GPS accuracy in metres, defaults to MatchOptions::default()
+    pub sigma: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MatchedTrace {
+    pub path: Vec<Coordinate>,
+    pub distance: f64,
+    // road network node each trace point was matched to, null when none was found
+    pub matched: Vec<Option<Coordinate>>,
+}
+
+#[post("/match", data = "<data>")]
+pub fn map_match(
+    token_raw: Token,
+    data: Json<MatchRequest>,
+    state: &State<Data>,
+) -> Result<Json<DataResponse<MatchedTrace>>, Custom<Json<ErrorResponse>>> {
+    let token_raw = token_raw.bearer();
+    if !authenticate(token_raw) {
+        let response = ErrorResponse {
+            message: "Invalid session token".to_string(),
+            code: None,
+        };
+        return Err(Custom(Status::Unauthorized, Json(response)));
+    }
+    if data.trace.is_empty() || data.trace.len() > MAX_TRACE_POINTS {
+        return Err(RoutingError::InvalidTrace { points: data.trace.len(), max: MAX_TRACE_POINTS }.into());
+    }
+    validate_coordinates(data.trace.iter())?;
+    let Some(network) = state.network(data.profile) else {
+        return Err(RoutingError::ProfileUnavailable(data.profile).into());
+    };
+
+    let mut options = MatchOptions::default();
+    match data.sigma {
+        Some(sigma) if !sigma.is_finite() || sigma <= 0.0 => return Err(RoutingError::InvalidSigma.into()),
+        Some(sigma) => options.sigma = sigma,
+        None => {}
+    }
+    let avoid = Avoid::default();
+    let candidates: Vec<Vec<usize>> = data
+        .trace
+        .iter()
+        .map(|point| snap_coordinate(state, network, point, &avoid))
+        .collect();
+
+    let mut workspace = state.workspaces.get();
+    let map = &state.map_id_to_coordinates;
+    let route = match_trace(&mut workspace, &network.graph, map, &data.trace, &candidates, &options)
+        .map_err(|e| RoutingError::MatchFailed(e.to_string()))?;
+
+    Ok(Json(DataResponse {
+        data: MatchedTrace {
+            path: route.nodes.iter().map(|node| map[node]).collect(),
+            distance: route.distance,
+            matched: route.matched.iter().map(|node| node.map(|node| map[&node])).collect(),
+        },
+    }))
+}
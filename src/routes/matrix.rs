@@ -0,0 +1,144 @@
+use crate::{
+    algo::{
+        avoid::Avoid,
+        error::RoutingError,
+        shortest_paths::{one_to_many, INFINITY},
+        turns::turn_aware_one_to_many,
+    },
+    global::Data,
+    routes::shortestpath::{snap_coordinate, validate_coordinates},
+    utils::{
+        auth_token::Token,
+        authenticate::authenticate,
+        coordinate::Coordinate,
+        profile::RoutingProfile,
+        response::{DataResponse, ErrorResponse},
+    },
+};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{http::Status, post, response::status::Custom, serde::json::Json, State};
+
+const MAX_MATRIX_SIZE: usize = 10000;
+// every source is one search over the network
+const MAX_SOURCES: usize = 100;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MatrixRequest {
+    pub sources: Vec<Coordinate>,
+    pub destinations: Vec<Coordinate>,
+    #[serde(default)]
+    pub profile: RoutingProfile,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Matrix {
+    // road network nodes the coordinates were snapped to, null when none was found
+    pub sources: Vec<Option<Coordinate>>,
+    pub destinations: Vec<Option<Coordinate>>,
+    // costs[i][j] goes from sources[i] to destinations[j], null when unreachable
+    pub costs: Vec<Vec<Option<f64>>>,
+    // number of null entries in costs
+    pub unreachable: usize,
+}
+
+#[post("/matrix", data = "<data>")]
+pub fn matrix(
+    token_raw: Token,
+    data: Json<MatrixRequest>,
+    state: &State<Data>,
+) -> Result<Json<DataResponse<Matrix>>, Custom<Json<ErrorResponse>>> {
+    let token_raw = token_raw.bearer();
+    if !authenticate(token_raw) {
+        let response = ErrorResponse {
+            message: "Invalid session token".to_string(),
+            code: None,
+        };
+        return Err(Custom(Status::Unauthorized, Json(response)));
+    }
+    if data.sources.len() > MAX_SOURCES {
+        return Err(RoutingError::TooManySources { sources: data.sources.len(), max: MAX_SOURCES }.into());
+    }
+    let size = data.sources.len().checked_mul(data.destinations.len());
+    if size.is_none_or(|size| size > MAX_MATRIX_SIZE) {
+        return Err(RoutingError::TooManyEntries { max: MAX_MATRIX_SIZE }.into());
+    }
+    // the destinations are counted after the sources
+    validate_coordinates(data.sources.iter().chain(data.destinations.iter()))?;
+    let Some(network) = state.network(data.profile) else {
+        return Err(RoutingError::ProfileUnavailable(data.profile).into());
+    };
+
+    let avoid = Avoid::default();
+    let snap = |coordinates: &[Coordinate]| -> Vec<Option<usize>> {
+        coordinates
+            .iter()
+            .map(|coordinate| {
+                snap_coordinate(state, network, coordinate, &avoid)
+                    .first()
+                    .copied()
+            })
+            .collect()
+    };
+    let sources = snap(&data.sources);
+    let destinations = snap(&data.destinations);
+    let targets: Vec<usize> = destinations.iter().flatten().copied().collect();
+
+    // traffic overlays only describe the car network
+    let traffic = state.traffic();
+    let edge_cost = |u: usize, v: usize, w: f64| match data.profile {
+        RoutingProfile::Car => Some(traffic.weight(u, v, w)),
+        _ => Some(w),
+    };
+
+    let mut workspace = state.workspaces.get();
+    let mut costs = vec![];
+    for source in sources.iter() {
+        let row = match source {
+            Some(src) => {
+                let found = match &network.turn_graph {
+                    Some(turn_graph) => turn_aware_one_to_many(
+                        &mut workspace,
+                        turn_graph,
+                        *src,
+                        &targets,
+                        INFINITY,
+                        &edge_cost,
+                    ),
+                    None => one_to_many(
+                        &mut workspace,
+                        &network.graph,
+                        *src,
+                        &targets,
+                        INFINITY,
+                        &edge_cost,
+                    ),
+                };
+                let mut found = found.into_iter();
+                destinations
+                    .iter()
+                    .map(|dest| dest.and_then(|_| found.next().flatten()))
+                    .collect()
+            }
+            None => vec![None; destinations.len()],
+        };
+        costs.push(row);
+    }
+
+    let to_coordinates = |nodes: &[Option<usize>]| -> Vec<Option<Coordinate>> {
+        nodes
+            .iter()
+            .map(|node| node.map(|node| state.map_id_to_coordinates[&node]))
+            .collect()
+    };
+    let unreachable = costs.iter().flatten().filter(|cost| cost.is_none()).count();
+    Ok(Json(DataResponse {
+        data: Matrix {
+            sources: to_coordinates(&sources),
+            destinations: to_coordinates(&destinations),
+            costs,
+            unreachable,
+        },
+    }))
+}
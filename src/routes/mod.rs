@@ -1,6 +1,11 @@
+pub mod alternatives;
 pub mod login;
+pub mod map_matching;
+pub mod matrix;
 pub mod shortestpath;
 pub mod signup;
+pub mod traffic;
 pub mod utils;
 pub mod history;
+pub mod isochrone;
 pub mod user;
@@ -1,15 +1,18 @@
 use crate::{
-    algo::{tsp_solver::TspSolver, shortest_paths::{harvesine_heuristic, astar, reconstruct_path}},
-    global::Data,
+    algo::{avoid::Avoid, edge_snap::{snap_to_edge, EdgeSnap}, elevation::Elevations, error::RoutingError, maneuvers::maneuvers, search_workspace::SearchWorkspace, time_dependent::{seconds_of_day, time_dependent_one_to_many, turn_aware_time_dependent_one_to_many, TimeDependentGraph}, traffic::WeightOverlay, tsp_solver::TspSolver, shortest_paths::{climb_penalty, harvesine_heuristic, astar_with_edge_costs, bidirectional_astar_with_workspace, multi_source_search, one_to_many, path_cost, EdgeCost, Route, INFINITY}, turns::{turn_aware_astar_with_edge_costs, turn_aware_multi_source_search, turn_aware_one_to_many}},
+    global::{Data, Network},
     utils::{
         auth_token::Token, authenticate::{authenticate, get_claims_by_token}, coordinate::Coordinate,
-        response::ErrorResponse, trip::{Trip, Location}, path::{Path, PathLocation},
+        response::ErrorResponse, trip::{Trip, Location}, path::{GeometryFormat, Leg, Path, PathLocation}, profile::RoutingProfile,
     }, db::{trips::create_trip, users::get_user_by_id},
 };
+use chrono::NaiveTime;
 use rocket::{http::Status, post, response::status::Custom, serde::json::Json, State};
-use serde::Serialize;
-use std::error::Error;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, sync::Arc};
 
+// a route needs somewhere to start and somewhere to go
+const MIN_STOPS: usize = 2;
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -24,16 +27,30 @@ pub fn shortestpath(
     mut data: Json<Trip>,
     state: &State<Data>,
 ) -> Result<Json<Path>, Custom<Json<ErrorResponse>>> {
-    let token_raw = token_raw.tkn.split(' ').collect::<Vec<&str>>()[1];
+    let token_raw = token_raw.bearer();
     if authenticate(token_raw) {
+        if data.locations.len() < MIN_STOPS {
+            return Err(RoutingError::TooFewStops { stops: data.locations.len(), min: MIN_STOPS }.into());
+        }
         let mut nodes: Vec<Coordinate> = Vec::new();
         for i in 0..data.locations.len() {
             data.locations[i].coordinates.id = data.locations[i].id;
             nodes.push(data.locations[i].coordinates);
         }
         
-        let mut tsp = TspSolver::new(&state.graph, &state.map_id_to_coordinates, nodes);
-        let results = tsp.held_karp_solve().unwrap();
+        let network = match state.network(data.profile) {
+            Some(network) => network,
+            None => return Err(RoutingError::ProfileUnavailable(data.profile).into()),
+        };
+        validate_locations(&data.locations)?;
+        let avoid = Avoid::new(&data.avoid_areas, &data.blocked_segments, &network.segment_tree);
+        let mut tsp = TspSolver::new(&network.graph, &state.map_id_to_coordinates, nodes);
+        // travel time profiles only describe the car network
+        let results = match (&data.start_time, &state.travel_times, data.profile) {
+            (Some(start_time), Some(travel_times), RoutingProfile::Car) => solve_time_dependent(state, &mut tsp, travel_times, start_time, &avoid),
+            _ => LegCosts::new(state, data.profile, &avoid, data.climb_penalty).and_then(|costs| solve_on_network(state, network, &mut tsp, &costs)),
+        };
+        let results = results?;
 
         let mut new_locations: Vec<Location> = Vec::new();
         for i in 0..results.len() {
@@ -45,8 +62,8 @@ pub fn shortestpath(
         }
         data.locations = new_locations;
 
-        let distance_path = build_path(&data.locations, &state);
-        let response = match distance_path {
+        let distance_path = build_path(&data.locations, &state, data.profile, &avoid, data.climb_penalty);
+        let mut response = match distance_path {
             Ok(d_p) => {
                 let mut path_aux: Vec<PathLocation> = Vec::new();
                 for i in 0..data.locations.len() {
@@ -54,99 +71,526 @@ pub fn shortestpath(
                 }
                 Path {
                     title: data.title.clone(),
-                    path: d_p.1,
+                    path: d_p.2,
                     distance: d_p.0,
-                    locations: path_aux
+                    cost: d_p.1,
+                    locations: path_aux,
+                    legs: d_p.3,
+                    polyline: None,
+                    ascent: None,
+                    descent: None,
                 }
             },
-            Err(message) => {
-                let response: ErrorResponse = ErrorResponse {
-                    message: message.to_string(),
-                };
-                return Err(Custom(Status::BadRequest, Json(response)));
-            }
+            Err(error) => return Err(error.into()),
         };
-        let token_claims = get_claims_by_token(token_raw).unwrap();
-        let user = get_user_by_id(&token_claims.uid).unwrap();
-
-        let _created_trip = create_trip(
-            &user.id,
-            &data.title,
-            &data.locations,
-            &response,
-            &response.distance,
-            &false,
-            &diesel::dsl::now
-        ).unwrap();
+        describe_legs(&mut response, state, &data);
+        let stored = compact_geometry(&mut response, &data);
+        save_trip(token_raw, &data, &stored)?;
 
         Ok(Json(response))
 
     } else {
         let response: ErrorResponse = ErrorResponse {
             message: "Invalid session token".to_string(),
+            code: None,
         };
         Err(Custom(Status::Unauthorized, Json(response)))
     }
 }
 
-pub fn approximate_coordinate(state: &State<Data>, coordinate: &Coordinate) -> Vec<Vec<f64>> {
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RouteRequest {
+    #[serde(flatten)]
+    pub trip: Trip,
+    // keep the route in the history, as /shortestpath always does
+    #[serde(default)]
+    pub save: bool,
+}
+
+// route through the locations in the order given, without solving the tour;
+// the legs of the response hold the distance of every leg
+#[post("/route", data = "<data>")]
+pub fn route(
+    token_raw: Token,
+    mut data: Json<RouteRequest>,
+    state: &State<Data>,
+) -> Result<Json<Path>, Custom<Json<ErrorResponse>>> {
+    let token_raw = token_raw.bearer();
+    if !authenticate(token_raw) {
+        let response: ErrorResponse = ErrorResponse {
+            message: "Invalid session token".to_string(),
+            code: None,
+        };
+        return Err(Custom(Status::Unauthorized, Json(response)));
+    }
+
+    let trip = &mut data.trip;
+    if trip.locations.len() < MIN_STOPS {
+        return Err(RoutingError::TooFewStops { stops: trip.locations.len(), min: MIN_STOPS }.into());
+    }
+    if trip.back_to_start {
+        let start = trip.locations[0].clone();
+        trip.locations.push(start);
+    }
+
+    validate_locations(&trip.locations)?;
+
+    let network = state.network(trip.profile).ok_or(RoutingError::ProfileUnavailable(trip.profile))?;
+    let avoid = Avoid::new(&trip.avoid_areas, &trip.blocked_segments, &network.segment_tree);
+    let (distance, cost, path, legs) = build_path(&trip.locations, state, trip.profile, &avoid, trip.climb_penalty)?;
+    let locations = trip
+        .locations
+        .iter()
+        .enumerate()
+        .map(|(i, location)| PathLocation { location: location.coordinates, label: format!("{}", i + 1) })
+        .collect();
+    let mut response = Path {
+        title: trip.title.clone(),
+        path,
+        distance,
+        cost,
+        locations,
+        legs,
+        polyline: None,
+        ascent: None,
+        descent: None,
+    };
+
+    describe_legs(&mut response, state, &data.trip);
+    let stored = compact_geometry(&mut response, &data.trip);
+    if data.save {
+        save_trip(token_raw, &data.trip, &stored)?;
+    }
+
+    Ok(Json(response))
+}
+
+fn validate_locations(locations: &[Location]) -> Result<(), RoutingError> {
+    validate_coordinates(locations.iter().map(|location| &location.coordinates))
+}
+
+// latitudes and longitudes must be within their ranges, stops are counted in
+// the order given
+pub fn validate_coordinates<'a>(coordinates: impl IntoIterator<Item = &'a Coordinate>) -> Result<(), RoutingError> {
+    for (stop, coordinate) in coordinates.into_iter().enumerate() {
+        if !(-90.0..=90.0).contains(&coordinate.lat) || !(-180.0..=180.0).contains(&coordinate.lng) {
+            return Err(RoutingError::InvalidCoordinate { stop });
+        }
+    }
+    Ok(())
+}
+
+// keeps the route in the history of the user the token belongs to
+fn save_trip(token_raw: &str, trip: &Trip, stored: &Path) -> Result<(), Custom<Json<ErrorResponse>>> {
+    let created_trip = get_claims_by_token(token_raw)
+        .ok()
+        .and_then(|token_claims| get_user_by_id(&token_claims.uid).ok())
+        .and_then(|user| {
+            create_trip(
+                &user.id,
+                &trip.title,
+                &trip.locations,
+                stored,
+                &stored.distance,
+                &trip.profile,
+                &false,
+                &diesel::dsl::now
+            ).ok()
+        });
+    match created_trip {
+        Some(_) => Ok(()),
+        None => {
+            let response: ErrorResponse = ErrorResponse {
+                message: "Could not save the trip".to_string(),
+                code: None,
+            };
+            Err(Custom(Status::InternalServerError, Json(response)))
+        }
+    }
+}
+
+// turn-by-turn instructions and climbs of every leg, which need the full geometry
+fn describe_legs(response: &mut Path, state: &State<Data>, trip: &Trip) {
+    let Some(network) = state.network(trip.profile) else {
+        return;
+    };
+    for leg in response.legs.iter_mut() {
+        let points = &response.path[leg.start..leg.end];
+        leg.maneuvers = maneuvers(points, &network.graph, &state.map_id_to_coordinates, &state.street_names, trip.language);
+        if let Some(elevations) = &state.elevations {
+            let (ascent, descent) = elevations.ascent_descent(points);
+            leg.ascent = Some(ascent);
+            leg.descent = Some(descent);
+        }
+    }
+    if state.elevations.is_some() {
+        response.ascent = Some(response.legs.iter().filter_map(|leg| leg.ascent).sum());
+        response.descent = Some(response.legs.iter().filter_map(|leg| leg.descent).sum());
+    }
+}
+
+// applies the geometry options of the trip to the response and returns the
+// compact copy kept in the history
+fn compact_geometry(response: &mut Path, trip: &Trip) -> Path {
+    if let Some(tolerance) = trip.tolerance {
+        response.simplify(tolerance);
+    }
+    let mut stored = response.clone();
+    stored.encode();
+    if trip.geometry == GeometryFormat::Polyline {
+        response.encode();
+    }
+    stored
+}
+
+// orders the stops evaluating every leg at the time the tour gets to it
+// Where the legs of every stop leave and arrive, the same ends build_path
+// routes between: either end of the arc the stop is snapped onto, with the
+// cost of the partial arc, or its snapped node when no arc is close enough.
+struct StopEnds {
+    edges: Vec<Option<EdgeSnap>>,
+    exits: Vec<Vec<(usize, f64)>>,
+    entries: Vec<Vec<(usize, f64)>>,
+    // nodes of all the entries, stop after stop
+    targets: Vec<usize>,
+}
+
+impl StopEnds {
+    fn new(state: &State<Data>, network: &Network, stops: &[Coordinate], avoid: &Avoid, edge_cost: &EdgeCost) -> Result<Self, RoutingError> {
+        let edges = snap_edges(network, stops, avoid);
+        let mut exits = vec![];
+        let mut entries = vec![];
+        for (stop, (coordinate, edge)) in stops.iter().zip(edges.iter()).enumerate() {
+            match edge {
+                Some(edge) => {
+                    exits.push(edge.exits(&network.graph, edge_cost));
+                    entries.push(edge.entries(&network.graph, edge_cost));
+                }
+                None => {
+                    let node = *snap_coordinate(state, network, coordinate, avoid).first().ok_or(RoutingError::SnapFailed { stop })?;
+                    exits.push(vec![(node, 0.0)]);
+                    entries.push(vec![(node, 0.0)]);
+                }
+            }
+        }
+        let targets = entries.iter().flatten().map(|&(node, _)| node).collect();
+        Ok(Self { edges, exits, entries, targets })
+    }
+
+    // cheapest leg from stop i to every stop, INFINITY when unreachable.
+    // search(exit, cost) gives the costs from exit to the targets when it is
+    // reached at cost.
+    fn costs_from(&self, i: usize, network: &Network, edge_cost: &EdgeCost, search: &mut dyn FnMut(usize, f64) -> Vec<Option<f64>>) -> Vec<f64> {
+        let mut best = vec![INFINITY; self.entries.len()];
+        best[i] = 0.0;
+        for &(exit, to_exit) in self.exits[i].iter() {
+            let mut found = search(exit, to_exit).into_iter();
+            for (j, ends) in self.entries.iter().enumerate() {
+                for &(_, from_entry) in ends.iter() {
+                    if let Some(cost) = found.next().flatten() {
+                        best[j] = best[j].min(to_exit + cost + from_entry);
+                    }
+                }
+            }
+        }
+        // stops on the same arc are driven along it
+        for (j, edge) in self.edges.iter().enumerate() {
+            if let (Some(src), Some(dest)) = (&self.edges[i], edge) {
+                if let Some(cost) = src.along(dest, &network.graph, edge_cost) {
+                    best[j] = best[j].min(cost);
+                }
+            }
+        }
+        best
+    }
+}
+
+// orders the stops on the costs of the routes between them over the network
+// of the profile, with the avoids, traffic and climb penalty of the request
+fn solve_on_network(state: &State<Data>, network: &Network, tsp: &mut TspSolver, costs: &LegCosts) -> Result<Vec<usize>, RoutingError> {
+    tsp.check_stops()?;
+    let edge_cost = |u: usize, v: usize, w: f64| costs.edge_cost(u, v, w);
+    let ends = StopEnds::new(state, network, &tsp.nodes, costs.avoid, &edge_cost)?;
+    let mut workspace = state.workspaces.get();
+    let matrix: Vec<Vec<f64>> = (0..tsp.nodes.len())
+        .map(|i| {
+            ends.costs_from(i, network, &edge_cost, &mut |exit, _| match &network.turn_graph {
+                Some(turn_graph) => turn_aware_one_to_many(&mut workspace, turn_graph, exit, &ends.targets, INFINITY, &edge_cost),
+                None => one_to_many(&mut workspace, &network.graph, exit, &ends.targets, INFINITY, &edge_cost),
+            })
+        })
+        .collect();
+    tsp.held_karp_solve_with_costs(&matrix)
+}
+
+// orders the stops evaluating every leg at the time the tour gets to it. The
+// partial arcs at the ends of the legs are driven at free-flow speed.
+fn solve_time_dependent(state: &State<Data>, tsp: &mut TspSolver, travel_times: &TimeDependentGraph, start_time: &NaiveTime, avoid: &Avoid) -> Result<Vec<usize>, RoutingError> {
+    let network = state.car_network();
+    let allowed = |u: usize, v: usize, travel: f64| avoid.allows_edge(u, v).then_some(travel);
+    let free_flow = |u: usize, v: usize, w: f64| avoid.allows_edge(u, v).then_some(w / travel_times.speed);
+    let ends = StopEnds::new(state, network, &tsp.nodes, avoid, &free_flow)?;
+    let mut workspace = state.workspaces.get();
+    let mut leg_times = |i: usize, departure: f64| {
+        ends.costs_from(i, network, &free_flow, &mut |exit, to_exit| match &network.turn_graph {
+            Some(turn_graph) => turn_aware_time_dependent_one_to_many(&mut workspace, turn_graph, travel_times, exit, &ends.targets, departure + to_exit, &allowed),
+            None => time_dependent_one_to_many(&mut workspace, &network.graph, travel_times, exit, &ends.targets, departure + to_exit, &allowed),
+        })
+    };
+    tsp.held_karp_solve_time_dependent(seconds_of_day(start_time), &mut leg_times)
+}
+
+pub fn approximate_coordinate(state: &State<Data>, coordinate: &Coordinate) -> Vec<Coordinate> {
     //try to approximate coordinate using kd-tree
     let latitude: f64 = coordinate.lat;
     let longitude: f64 = coordinate.lng;
     let nodes = state
         .kd_tree
-        .get_n_nearest_neighbor(&vec![latitude, longitude], 5)
-        .unwrap();
+        .get_n_nearest_neighbor(&[latitude, longitude], 5)
+        .into_iter()
+        .map(|node| Coordinate { lat: node.point[0], lng: node.point[1], id: *node.item })
+        .collect();
    nodes
 }
 
-pub fn build_path(path: &Vec<Location>, state: &State<Data>) -> Result<(f64, Vec<Coordinate>), Box<dyn Error>> {
-    let mut new_path: Vec<Coordinate> = vec![];
-    let mut distance:f64 = 0.;
-    for i in 0..path.len() - 1 {
-
-        let start_approximation = approximate_coordinate(state, &path[i].coordinates);
-        let end_approximation: Vec<Vec<f64>> = approximate_coordinate(state, &path[i+1].coordinates);
-
-        let mut resolved_path: bool = false;
-        for e in 0..start_approximation.len() {
-            if resolved_path { break; }
-
-            let min_len = if e < end_approximation.len() { e } else { end_approximation.len() };
-            for j in 0..min_len {
-                let src: usize = start_approximation[e][2] as usize;
-                let dest: usize = end_approximation[j][2] as usize;
-                let dijkstra_result = astar(&state.graph, &state.map_id_to_coordinates, src, dest, &harvesine_heuristic);
-                
-                match dijkstra_result {
-                    Ok(ok_path) => {
-                        distance += ok_path.0;
-                        let results = reconstruct_path(ok_path.1, dest).unwrap();
-
-                        for r in results {
-                            let node = state.map_id_to_coordinates.get(&r).unwrap().clone();
-                            new_path.push(node);
-                        }
-                        resolved_path = true;
-                        break;
-                    },
-                    Err(_) => {
-                        continue;
-                    }
-                }
-            }
-        }
+// Nearest nodes to coordinate outside the avoided areas. Nodes in the main
+// strongly connected component come first, as any two of them are reachable
+// from each other. When none is close enough it gives the nearest node of
+// every other component, the ones leading to or from the main component
+// first, so the legs can look for a pair of stops that connect.
+pub fn snap_coordinate(state: &State<Data>, network: &Network, coordinate: &Coordinate, avoid: &Avoid) -> Vec<usize> {
+    let nearest: Vec<usize> = state
+        .kd_tree
+        .get_n_nearest_neighbor(&[coordinate.lat, coordinate.lng], 50)
+        .into_iter()
+        .filter(|node| avoid.allows_coordinate(&Coordinate { lat: node.point[0], lng: node.point[1], id: *node.item }))
+        .map(|node| *node.item)
+        .collect();
+    let main: Vec<usize> = nearest.iter().copied().filter(|&node| network.components.in_largest(node)).take(5).collect();
+    if !main.is_empty() {
+        return main;
+    }
+    let components = &network.components;
+    let mut seen = HashSet::new();
+    let mut fallback: Vec<usize> = nearest.into_iter().filter(|&node| seen.insert(components.component[node])).collect();
+    fallback.sort_by_key(|&node| {
+        let c = components.component[node];
+        !components.component_reaches(c, components.largest) && !components.component_reaches(components.largest, c)
+    });
+    fallback.truncate(5);
+    fallback
+}
 
-        if !resolved_path {
-            return Err("No path found".into())
+// per-request changes to the arc weights of the route
+struct LegCosts<'a> {
+    avoid: &'a Avoid,
+    traffic: Arc<WeightOverlay>,
+    // elevations and the penalty per metre climbed
+    climb: Option<(&'a Elevations, f64)>,
+}
+
+impl<'a> LegCosts<'a> {
+    fn new(state: &'a State<Data>, profile: RoutingProfile, avoid: &'a Avoid, climb_penalty: Option<f64>) -> Result<Self, RoutingError> {
+        // traffic overlays only describe the car network
+        let traffic = match profile {
+            RoutingProfile::Car => state.traffic(),
+            _ => Arc::new(WeightOverlay::default()),
+        };
+        let climb = match (climb_penalty, &state.elevations) {
+            (Some(penalty), _) if !penalty.is_finite() || penalty < 0.0 => return Err(RoutingError::InvalidClimbPenalty),
+            (Some(penalty), Some(elevations)) => Some((elevations, penalty)),
+            (Some(_), None) => return Err(RoutingError::ElevationUnavailable),
+            (None, _) => None,
+        };
+        Ok(LegCosts { avoid, traffic, climb })
+    }
+
+    fn is_plain(&self) -> bool {
+        self.avoid.is_empty() && self.traffic.is_empty() && self.climb.is_none()
+    }
+
+    fn edge_cost(&self, u: usize, v: usize, w: f64) -> Option<f64> {
+        if !self.avoid.allows_edge(u, v) {
+            return None;
+        }
+        let w = self.traffic.weight(u, v, w);
+        match self.climb {
+            Some((elevations, penalty)) => climb_penalty(elevations, penalty)(u, v, w),
+            None => Some(w),
         }
     }
+}
+
+// the hierarchy models neither turns, per-request restrictions, traffic nor
+// climbs, so it is only used when none of them applies
+fn route_leg(state: &State<Data>, network: &Network, workspace: &mut SearchWorkspace, src: usize, dest: usize, costs: &LegCosts) -> Result<Route, RoutingError> {
+    let map = &state.map_id_to_coordinates;
+    let edge_cost = |u: usize, v: usize, w: f64| costs.edge_cost(u, v, w);
+    if let Some(turn_graph) = &network.turn_graph {
+        return match &network.landmarks {
+            Some(landmarks) => turn_aware_astar_with_edge_costs(workspace, turn_graph, map, src, dest, &|_, v, t| landmarks.heuristic(v, t), &edge_cost),
+            None => turn_aware_astar_with_edge_costs(workspace, turn_graph, map, src, dest, &harvesine_heuristic, &edge_cost),
+        };
+    }
+    if !costs.is_plain() {
+        return match &network.landmarks {
+            Some(landmarks) => astar_with_edge_costs(workspace, &network.graph, map, src, dest, &|_, v, t| landmarks.heuristic(v, t), &edge_cost),
+            None => astar_with_edge_costs(workspace, &network.graph, map, src, dest, &harvesine_heuristic, &edge_cost),
+        };
+    }
+    match (&network.contraction_hierarchy, &network.landmarks) {
+        (Some(hierarchy), _) => hierarchy.shortest_path(src, dest),
+        (None, Some(landmarks)) => bidirectional_astar_with_workspace(workspace, &network.graph, &network.reverse_graph, map, src, dest, &|_, v, t| landmarks.heuristic(v, t)),
+        (None, None) => bidirectional_astar_with_workspace(workspace, &network.graph, &network.reverse_graph, map, src, dest, &harvesine_heuristic),
+    }
+}
+
+// total distance and cost, coordinates of the route and where each leg lies
+// in them
+pub type BuiltPath = (f64, f64, Vec<Coordinate>, Vec<Leg>);
+
+// cost and metres driven of a leg, and its coordinates
+type LegRoute = (f64, f64, Vec<Coordinate>);
+
+pub fn build_path(path: &Vec<Location>, state: &State<Data>, profile: RoutingProfile, avoid: &Avoid, climb_penalty: Option<f64>) -> Result<BuiltPath, RoutingError> {
+    let network = state.network(profile).ok_or(RoutingError::ProfileUnavailable(profile))?;
+    let mut routes: Vec<LegRoute> = vec![];
+    let mut workspace = state.workspaces.get();
+    let costs = LegCosts::new(state, profile, avoid, climb_penalty)?;
+    let approximations: Vec<Vec<usize>> = path
+        .iter()
+        .map(|location| snap_coordinate(state, network, &location.coordinates, avoid))
+        .collect();
+    if let Some(stop) = approximations.iter().position(|nodes| nodes.is_empty()) {
+        return Err(RoutingError::SnapFailed { stop });
+    }
+    let stops: Vec<Coordinate> = path.iter().map(|location| location.coordinates).collect();
+    let edges = snap_edges(network, &stops, avoid);
 
-    return Ok((distance, new_path));
+    for (i, pair) in edges.windows(2).enumerate() {
+        let by_edges = match (&pair[0], &pair[1]) {
+            (Some(src), Some(dest)) => route_between_edges(state, network, &mut workspace, src, dest, &costs),
+            _ => None,
+        };
+        // e.g. when the stops snap onto arcs that only lead out of the network
+        let leg = by_edges.or_else(|| route_between_nodes(state, network, &mut workspace, &approximations[i], &approximations[i + 1], &costs));
+        let Some(leg) = leg else {
+            return Err(RoutingError::UnreachableLeg { from: i, to: i + 1 });
+        };
+        routes.push(leg);
+    }
+
+    return Ok(assemble_legs(&stops, routes));
+}
+
+// arcs the stops lie on, the ones in the main component first like the
+// snapped nodes
+fn snap_edges(network: &Network, stops: &[Coordinate], avoid: &Avoid) -> Vec<Option<EdgeSnap>> {
+    let in_largest = |u: usize, v: usize| network.components.in_largest(u) && network.components.in_largest(v);
+    stops
+        .iter()
+        .map(|stop| {
+            snap_to_edge(&network.segment_tree, stop, &|u, v| avoid.allows_edge(u, v) && in_largest(u, v))
+                .or_else(|| snap_to_edge(&network.segment_tree, stop, &|u, v| avoid.allows_edge(u, v)))
+        })
+        .collect()
+}
+
+// joins the routes between consecutive stops into one path, with a leg for
+// every one of them
+fn assemble_legs(stops: &[Coordinate], routes: Vec<LegRoute>) -> BuiltPath {
+    let mut new_path: Vec<Coordinate> = vec![];
+    let mut distance: f64 = 0.;
+    let mut cost: f64 = 0.;
+    let mut legs: Vec<Leg> = vec![];
+    for (i, (leg_cost, leg_distance, coordinates)) in routes.into_iter().enumerate() {
+        distance += leg_distance;
+        cost += leg_cost;
+        let start = new_path.len();
+        let start_offset = stops[i].distance_to(&coordinates[0]);
+        let end_offset = stops[i + 1].distance_to(&coordinates[coordinates.len() - 1]);
+        new_path.extend(coordinates);
+        legs.push(Leg {
+            from: i,
+            to: i + 1,
+            distance: leg_distance,
+            cost: leg_cost,
+            start,
+            end: new_path.len(),
+            cumulative_distance: distance,
+            start_offset,
+            end_offset,
+            maneuvers: vec![],
+            ascent: None,
+            descent: None,
+        });
+    }
+    (distance, cost, new_path, legs)
+}
+
+// route between two points in the middle of road arcs, leaving src and
+// reaching dest through either end of their arcs in a single search. With
+// turn restrictions the search runs over the arcs themselves, so the turns
+// off src and onto dest are checked too.
+fn route_between_edges(state: &State<Data>, network: &Network, workspace: &mut SearchWorkspace, src: &EdgeSnap, dest: &EdgeSnap, costs: &LegCosts) -> Option<LegRoute> {
+    let map = &state.map_id_to_coordinates;
+    let edge_cost = |u: usize, v: usize, w: f64| costs.edge_cost(u, v, w);
+    let exits = src.exits(&network.graph, &edge_cost);
+    let entries = dest.entries(&network.graph, &edge_cost);
+    let estimate = |v: usize, t: usize| match &network.landmarks {
+        Some(landmarks) => landmarks.heuristic(v, t),
+        None => harvesine_heuristic(map, v, t),
+    };
+    let potential = |v: usize| entries.iter().map(|&(entry, _)| estimate(v, entry)).fold(INFINITY, f64::min);
+    // the condensation rules out stops in components that do not connect
+    // without searching the whole network
+    let connected = exits.iter().any(|&(exit, _)| entries.iter().any(|&(entry, _)| network.components.reaches(exit, entry)));
+    let through = match &network.turn_graph {
+        _ if !connected => Err(RoutingError::NoPath),
+        Some(turn_graph) => {
+            let sources: Vec<(usize, f64)> = exits.iter().filter_map(|&(exit, cost)| Some((turn_graph.arc(src.other_end(exit), exit)?, cost))).collect();
+            let targets: Vec<(usize, f64)> = entries.iter().filter_map(|&(entry, cost)| Some((turn_graph.arc(entry, dest.other_end(entry))?, cost))).collect();
+            turn_aware_multi_source_search(workspace, turn_graph, &sources, &targets, &potential, &edge_cost)
+        }
+        None => multi_source_search(workspace, &network.graph, &exits, &entries, INFINITY, &potential, &edge_cost),
+    };
+    let along = src.along(dest, &network.graph, &edge_cost).map(|cost| (cost, vec![]));
+    let best = match (along, through.ok()) {
+        (Some(along), Some(through)) if through.0 < along.0 => Some(through),
+        (Some(along), _) => Some(along),
+        (None, through) => through,
+    };
+    let (cost, nodes) = best?;
+    // the same route measured with the plain weights of its arcs
+    let length = |_: usize, _: usize, w: f64| Some(w);
+    let distance = match (nodes.first(), nodes.last()) {
+        (Some(&exit), Some(&entry)) => {
+            let exit_length = src.exits(&network.graph, &length).into_iter().find(|&(node, _)| node == exit)?.1;
+            let entry_length = dest.entries(&network.graph, &length).into_iter().find(|&(node, _)| node == entry)?.1;
+            exit_length + path_cost(&network.graph, &nodes) + entry_length
+        }
+        _ => src.along(dest, &network.graph, &length)?,
+    };
+    let mut coordinates = vec![src.point];
+    coordinates.extend(nodes.iter().map(|node| map[node]));
+    coordinates.push(dest.point);
+    Some((cost, distance, coordinates))
+}
+
+// route between the nearest snapped nodes; nodes in the main component come
+// first, so the first pair is connected whenever any pair of them is
+fn route_between_nodes(state: &State<Data>, network: &Network, workspace: &mut SearchWorkspace, sources: &[usize], destinations: &[usize], costs: &LegCosts) -> Option<LegRoute> {
+    // nearest pair of candidates that can reach each other at all
+    let (src, dest) = sources.iter().flat_map(|&src| destinations.iter().map(move |&dest| (src, dest))).find(|&(src, dest)| network.components.reaches(src, dest))?;
+    let (cost, nodes) = route_leg(state, network, workspace, src, dest, costs).ok()?;
+    let coordinates = nodes.iter().map(|node| state.map_id_to_coordinates[node]).collect();
+    Some((cost, path_cost(&network.graph, &nodes), coordinates))
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::routes::utils::compare_coordinates_approximation;
 
     #[test]
@@ -154,4 +598,35 @@ mod tests {
     fn test_compare_coordinates_approximation() {
         compare_coordinates_approximation();
     }
+
+    #[test]
+    fn test_validate_coordinates() {
+        let point = |lat: f64, lng: f64| Coordinate { lat, lng, id: 0 };
+        assert_eq!(validate_coordinates(&[point(52.0, 13.0), point(-90.0, 180.0)]), Ok(()));
+        assert_eq!(validate_coordinates(&[point(52.0, 13.0), point(91.0, 13.0)]), Err(RoutingError::InvalidCoordinate { stop: 1 }));
+        assert_eq!(validate_coordinates(&[point(f64::NAN, 13.0)]), Err(RoutingError::InvalidCoordinate { stop: 0 }));
+    }
+
+    #[test]
+    fn test_assemble_legs() {
+        let point = |lat: f64, lng: f64| Coordinate { lat, lng, id: 0 };
+        let stops = [point(52.0, 13.0), point(52.01, 13.0), point(52.02, 13.01)];
+        // the last stop lies beside the road it is reached on
+        let routes = vec![
+            (12.0, 10.0, vec![point(52.0, 13.0), point(52.01, 13.0)]),
+            (5.0, 4.0, vec![point(52.01, 13.0), point(52.015, 13.0), point(52.02, 13.0)]),
+        ];
+        let (distance, cost, path, legs) = assemble_legs(&stops, routes);
+        assert_eq!((distance, cost), (14.0, 17.0));
+        assert_eq!(path.len(), 5);
+        assert_eq!(legs.len(), 2);
+        assert_eq!((legs[0].from, legs[0].to, legs[0].start, legs[0].end), (0, 1, 0, 2));
+        assert_eq!((legs[1].from, legs[1].to, legs[1].start, legs[1].end), (1, 2, 2, 5));
+        assert_eq!((legs[0].distance, legs[0].cost, legs[0].cumulative_distance), (10.0, 12.0, 10.0));
+        assert_eq!((legs[1].distance, legs[1].cost, legs[1].cumulative_distance), (4.0, 5.0, 14.0));
+        assert_eq!(path[legs[1].start], stops[1]);
+        assert_eq!((legs[0].start_offset, legs[0].end_offset, legs[1].start_offset), (0.0, 0.0, 0.0));
+        assert_eq!(legs[1].end_offset, stops[2].distance_to(&point(52.02, 13.0)));
+        assert!(legs[1].end_offset > 600.0);
+    }
 }
@@ -26,7 +26,8 @@ fn check_email(email: &str) -> bool {
 pub fn sign_up(body: Json<Body<'_>>) -> Result<Json<OkResponse>, Custom<Json<ErrorResponse>>>{
     if !check_email(body.email) {
         let response = ErrorResponse {
-            message: "Wrong data. Please check your data".to_string()
+            message: "Wrong data. Please check your data".to_string(),
+            code: None,
         };
         return Err(Custom(Status::BadRequest, Json(response)))
     }
@@ -82,7 +83,8 @@ pub fn sign_up(body: Json<Body<'_>>) -> Result<Json<OkResponse>, Custom<Json<Err
 
     
                 let response = ErrorResponse {
-                    message
+                    message,
+                    code: None,
                 };
                 Err(Custom(Status::BadRequest, Json(response)))
             },
@@ -0,0 +1,75 @@
+use crate::{
+    algo::traffic::{TrafficUpdate, WeightOverlay},
+    db::users::get_user_by_id,
+    global::Data,
+    utils::{
+        auth_token::Token,
+        authenticate::{authenticate, get_claims_by_token},
+        response::{ErrorResponse, OkResponse},
+    },
+};
+use rocket::{delete, http::Status, post, response::status::Custom, serde::json::Json, State};
+
+fn check_admin(token_raw: &Token) -> Result<(), Custom<Json<ErrorResponse>>> {
+    let token_raw = token_raw.bearer();
+    if !authenticate(token_raw) {
+        let response = ErrorResponse {
+            message: "Invalid session token".to_string(),
+            code: None,
+        };
+        return Err(Custom(Status::Unauthorized, Json(response)));
+    }
+    let token_claims = get_claims_by_token(token_raw).unwrap();
+    match get_user_by_id(&token_claims.uid) {
+        Ok(user) if user.admin && user.username == token_claims.username => Ok(()),
+        _ => {
+            let response = ErrorResponse {
+                message: "Admin privileges required".to_string(),
+                code: None,
+            };
+            Err(Custom(Status::Forbidden, Json(response)))
+        }
+    }
+}
+
+// replaces the current overlay, requests already running keep the old one
+#[post("/", data = "<data>")]
+pub fn upload_traffic(
+    token_raw: Token,
+    data: Json<Vec<TrafficUpdate>>,
+    state: &State<Data>,
+) -> Result<Json<OkResponse>, Custom<Json<ErrorResponse>>> {
+    check_admin(&token_raw)?;
+    match WeightOverlay::new(&state.car_network().graph, &data) {
+        Ok(overlay) => {
+            let message = format!("Traffic overlay updated with {} edges", overlay.len());
+            state.set_traffic(overlay);
+            Ok(Json(OkResponse {
+                message,
+                token: None,
+                username: None,
+            }))
+        }
+        Err(message) => {
+            let response = ErrorResponse {
+                message: message.to_string(),
+                code: None,
+            };
+            Err(Custom(Status::BadRequest, Json(response)))
+        }
+    }
+}
+
+#[delete("/")]
+pub fn clear_traffic(
+    token_raw: Token,
+    state: &State<Data>,
+) -> Result<Json<OkResponse>, Custom<Json<ErrorResponse>>> {
+    check_admin(&token_raw)?;
+    state.set_traffic(WeightOverlay::default());
+    Ok(Json(OkResponse {
+        message: "Traffic overlay cleared".to_string(),
+        token: None,
+        username: None,
+    }))
+}
@@ -3,7 +3,7 @@ use crate::{utils::{auth_token::Token, user::UserBriefDetails, response::ErrorRe
 
 #[get("/")]
 pub fn get_user_details(token_raw: Token) -> Result<Json<UserBriefDetails>, Custom<Json<ErrorResponse>>> {
-    let token_raw = token_raw.tkn.split(' ').collect::<Vec<&str>>()[1];
+    let token_raw = token_raw.bearer();
     if authenticate(token_raw) {
         let token_claims = get_claims_by_token(token_raw).unwrap();
         let user = get_user_by_id(&token_claims.uid).unwrap();
@@ -21,6 +21,7 @@ pub fn get_user_details(token_raw: Token) -> Result<Json<UserBriefDetails>, Cust
     }
     let response = ErrorResponse {
         message: "Invalid Token".to_string(),
+        code: None,
     };
     Err(Custom(Status::Unauthorized, Json(response)))
 }
\ No newline at end of file
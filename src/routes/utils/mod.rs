@@ -79,7 +79,7 @@ pub fn compare_coordinates_approximation() {
     for test_coordinate in tests.iter() {
         let start = Instant::now();
         let _nearest_coordinate = kd_tree
-            .nearest_neighbor(&kd_tree.root, &vec![test_coordinate.lat, test_coordinate.lng], 0)
+            .get_nearest_neighbor(&[test_coordinate.lat, test_coordinate.lng])
             .unwrap();
         let duration = start.elapsed();
         times_elapsed.push(duration);
@@ -11,6 +11,8 @@ diesel::table! {
         distance -> Float8,
         completed -> Bool,
         created_on -> Timestamp,
+        #[max_length = 20]
+        profile -> Varchar,
     }
 }
 
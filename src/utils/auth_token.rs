@@ -20,4 +20,11 @@ impl<'r> FromRequest<'r> for Token<'r> {
             None => Outcome::Forward(()),
         }
     }
-}
\ No newline at end of file
+}
+
+impl<'r> Token<'r> {
+    // the token of an "Authorization: Bearer <token>" header, empty when malformed
+    pub fn bearer(&self) -> &'r str {
+        self.tkn.split_once(' ').map_or("", |(_, token)| token)
+    }
+}
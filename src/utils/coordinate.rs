@@ -1,9 +1,20 @@
+use geoutils::Location;
 use rocket::serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug, Copy, Clone)]
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq)]
 #[serde(crate = "rocket::serde")]
 pub struct Coordinate {
     pub lat: f64,
     pub lng: f64,
+    #[serde(default)]
     pub id: usize
 }
+
+impl Coordinate {
+    // great-circle distance in metres
+    pub fn distance_to(&self, other: &Coordinate) -> f64 {
+        Location::new(self.lat, self.lng)
+            .haversine_distance_to(&Location::new(other.lat, other.lng))
+            .meters()
+    }
+}
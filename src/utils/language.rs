@@ -0,0 +1,10 @@
+use rocket::serde::{Deserialize, Serialize};
+
+// language of the turn-by-turn instructions
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Es,
+}
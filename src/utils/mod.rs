@@ -3,13 +3,23 @@ pub mod authenticate;
 pub mod claims;
 pub mod coordinate;
 pub mod hash;
+pub mod language;
 pub mod response;
 pub mod salt;
 pub mod trip;
 pub mod path;
+pub mod polyline;
+pub mod profile;
 pub mod user;
 
 pub use crate::ds::{graph::Graph, kdtree::KdTree};
+use crate::algo::contraction_hierarchies::ContractionHierarchy;
+use crate::algo::elevation::{parse_elevations, Elevations};
+use crate::algo::landmarks::Landmarks;
+use crate::algo::maneuvers::{parse_street_names, StreetNames};
+use crate::algo::time_dependent::{parse_profiles, TimeDependentGraph};
+use crate::algo::traffic::{TrafficUpdate, WeightOverlay};
+use crate::algo::turns::{parse_turn_restrictions, parse_u_turn_penalty, TurnGraph};
 use coordinate::Coordinate;
 use std::collections::HashMap;
 use std::error::Error;
@@ -75,38 +85,94 @@ pub fn create_id_to_coordinates_hashmap_from_file(
     Ok(coordinates_hashmap)
 }
 
+// kd-tree of the coordinates file keyed by node id, with great-circle distances
 pub fn create_kd_tree_from_file(
     coordinates_file: &str,
-) -> Result<KdTree<f64>, Box<dyn Error>> {
+) -> Result<KdTree<usize>, Box<dyn Error>> {
     let file = fs::read_to_string(coordinates_file)?;
     let mut points = vec![];
     for line in file.lines() {
         let mut split_line = line.split_whitespace();
-        let id = split_line
-            .next()
-            .unwrap()
-            .to_string()
-            .parse::<f64>()
-            .unwrap();
-        let latitude = split_line
-            .next()
-            .unwrap()
-            .to_string()
-            .parse::<f64>()
-            .unwrap();
-        let longitude = split_line
-            .next()
-            .unwrap()
-            .to_string()
-            .parse::<f64>()
-            .unwrap();
+        let Some(id) = split_line.next() else {
+            continue;
+        };
+        let id = id.parse::<usize>()?;
+        let latitude = split_line.next().ok_or("Missing latitude")?.parse::<f64>()?;
+        let longitude = split_line.next().ok_or("Missing longitude")?.parse::<f64>()?;
 
-        points.push(vec![latitude, longitude, id])
+        points.push(([latitude, longitude], id))
     }
     let tree = KdTree::new(points);
     Ok(tree)
 }
 
+// loads the hierarchy preprocessed for graph; building it is an offline step,
+// `cargo run --release --bin preprocess`, so a missing or stale file is an error
+pub fn create_contraction_hierarchy_from_file(
+    hierarchy_file: &str,
+    graph: &Graph,
+) -> Result<ContractionHierarchy, Box<dyn Error>> {
+    let hierarchy = ContractionHierarchy::load(hierarchy_file).map_err(|error| {
+        format!("Could not load {} ({}), build it with the preprocess binary", hierarchy_file, error)
+    })?;
+    if hierarchy.fingerprint != graph.fingerprint() {
+        return Err(format!("{} was built for other arcs, build it again with the preprocess binary", hierarchy_file).into());
+    }
+    Ok(hierarchy)
+}
+
+// loads the landmark distance tables preprocessed for graph, built offline
+// like the contraction hierarchy
+pub fn create_landmarks_from_file(
+    landmarks_file: &str,
+    graph: &Graph,
+) -> Result<Landmarks, Box<dyn Error>> {
+    let landmarks = Landmarks::load(landmarks_file).map_err(|error| {
+        format!("Could not load {} ({}), build it with the preprocess binary", landmarks_file, error)
+    })?;
+    landmarks.validate(graph).map_err(|error| {
+        format!("{}: {}, build it again with the preprocess binary", landmarks_file, error)
+    })?;
+    Ok(landmarks)
+}
+
+// builds the edge-expanded graph from a file of turn restrictions and penalties
+pub fn create_turn_graph_from_file(
+    restrictions_file: &str,
+    graph: &Graph,
+    u_turn_penalty: Option<&str>,
+) -> Result<TurnGraph, Box<dyn Error>> {
+    let content = fs::read_to_string(restrictions_file)?;
+    let restrictions = parse_turn_restrictions(&content, parse_u_turn_penalty(u_turn_penalty)?)?;
+    Ok(TurnGraph::new(graph, &restrictions))
+}
+
+// loads the travel time profiles, arcs without one are driven at speed in m/s
+pub fn create_time_dependent_graph_from_file(
+    profiles_file: &str,
+    speed: f64,
+) -> Result<TimeDependentGraph, Box<dyn Error>> {
+    let profiles = parse_profiles(&fs::read_to_string(profiles_file)?)?;
+    Ok(TimeDependentGraph { profiles, speed })
+}
+
+// loads the traffic overlay in effect at startup, a JSON list of updates
+pub fn create_weight_overlay_from_file(
+    traffic_file: &str,
+    graph: &Graph,
+) -> Result<WeightOverlay, Box<dyn Error>> {
+    let updates: Vec<TrafficUpdate> = serde_json::from_str(&fs::read_to_string(traffic_file)?)?;
+    WeightOverlay::new(graph, &updates)
+}
+
+pub fn create_street_names_from_file(names_file: &str) -> Result<StreetNames, Box<dyn Error>> {
+    parse_street_names(&fs::read_to_string(names_file)?)
+}
+
+pub fn create_elevations_from_file(elevation_file: &str) -> Result<Elevations, Box<dyn Error>> {
+    parse_elevations(&fs::read_to_string(elevation_file)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,4 +190,20 @@ mod tests {
     fn create_kd_tree_from_file_correct() {
         let _tree = create_kd_tree_from_file(&("nodes.txt".to_string())).unwrap();
     }
+
+    #[test]
+    fn create_contraction_hierarchy_from_file_checks_graph() {
+        let mut g = Graph::new(3);
+        g.add_edge(0, 1, 1.0);
+        g.add_edge(1, 2, 1.0);
+        let file = std::env::temp_dir().join(format!("hierarchy-{}.json", std::process::id()));
+        let file = file.to_str().unwrap();
+        assert!(create_contraction_hierarchy_from_file(file, &g).is_err());
+
+        ContractionHierarchy::new(&g).save(file).unwrap();
+        assert!(create_contraction_hierarchy_from_file(file, &g).is_ok());
+        g.add_edge(2, 0, 1.0);
+        assert!(create_contraction_hierarchy_from_file(file, &g).is_err());
+        fs::remove_file(file).unwrap();
+    }
 }
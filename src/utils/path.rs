@@ -1,17 +1,180 @@
 use serde::{Serialize, Deserialize};
+use std::error::Error;
 
-use crate::utils::Coordinate;
+use crate::algo::{maneuvers::Maneuver, simplify::douglas_peucker};
+use crate::utils::{polyline, Coordinate};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct  PathLocation {
     pub location: Coordinate,
     pub label: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Leg {
+    // indices into locations
+    pub from: usize,
+    pub to: usize,
+    // metres driven
+    pub distance: f64,
+    // what the route minimised: the distance plus traffic delays and climb
+    // penalties
+    #[serde(default)]
+    pub cost: f64,
+    // the leg is path[start..end]
+    pub start: usize,
+    pub end: usize,
+    // distance from the first location to the end of this leg
+    pub cumulative_distance: f64,
+    // metres from the locations to where the leg starts and ends on the road
+    #[serde(default)]
+    pub start_offset: f64,
+    #[serde(default)]
+    pub end_offset: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maneuvers: Vec<Maneuver>,
+    // metres climbed and descended, when elevations are loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ascent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descent: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GeometryFormat {
+    #[default]
+    Coordinates,
+    // Google encoded polyline in Path.polyline, leaving Path.path empty
+    Polyline,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Path {
     pub title: String,
     pub path: Vec<Coordinate>,
     pub distance: f64,
-    pub locations: Vec<PathLocation>
+    // sum of the costs of the legs
+    #[serde(default)]
+    pub cost: f64,
+    pub locations: Vec<PathLocation>,
+    // trips saved before legs were added have none
+    #[serde(default)]
+    pub legs: Vec<Leg>,
+    // leg ranges index into the decoded points when the path is encoded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub polyline: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ascent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descent: Option<f64>,
+}
+
+impl Path {
+    // simplifies every leg on its own, so stops stay where legs start and end
+    pub fn simplify(&mut self, tolerance: f64) {
+        if self.legs.is_empty() {
+            let kept = douglas_peucker(&self.path, tolerance);
+            self.path = kept.iter().map(|&i| self.path[i]).collect();
+            return;
+        }
+        let mut simplified = vec![];
+        for leg in self.legs.iter_mut() {
+            let points = &self.path[leg.start..leg.end];
+            let start = simplified.len();
+            simplified.extend(douglas_peucker(points, tolerance).iter().map(|&i| points[i]));
+            leg.start = start;
+            leg.end = simplified.len();
+        }
+        self.path = simplified;
+    }
+
+    pub fn encode(&mut self) {
+        if self.polyline.is_none() {
+            self.polyline = Some(polyline::encode(&self.path));
+            self.path = vec![];
+        }
+    }
+
+    // puts the points of an encoded path back into path
+    pub fn decode(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(encoded) = &self.polyline {
+            self.path = polyline::decode(encoded)?;
+            self.polyline = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_path() -> Path {
+        let path: Vec<Coordinate> = (0..10)
+            .map(|i| Coordinate { lat: 4.6 + f64::from(i) * 0.001, lng: -74.08, id: i as usize })
+            .collect();
+        let leg = |from: usize, start: usize, end: usize| Leg {
+            from,
+            to: from + 1,
+            distance: 0.0,
+            cost: 0.0,
+            start,
+            end,
+            cumulative_distance: 0.0,
+            start_offset: 0.0,
+            end_offset: 0.0,
+            maneuvers: vec![],
+            ascent: None,
+            descent: None,
+        };
+        Path {
+            title: "trip".to_string(),
+            path,
+            distance: 0.0,
+            cost: 0.0,
+            locations: vec![],
+            legs: vec![leg(0, 0, 4), leg(1, 4, 10)],
+            polyline: None,
+            ascent: None,
+            descent: None,
+        }
+    }
+
+    #[test]
+    fn test_simplify_keeps_leg_ends() {
+        let mut path = straight_path();
+        path.simplify(10.0);
+        let ids: Vec<usize> = path.path.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![0, 3, 4, 9]);
+        assert_eq!((path.legs[0].start, path.legs[0].end), (0, 2));
+        assert_eq!((path.legs[1].start, path.legs[1].end), (2, 4));
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut path = straight_path();
+        let points = path.path.clone();
+        path.encode();
+        assert!(path.path.is_empty());
+        let decoded = polyline::decode(path.polyline.as_ref().unwrap()).unwrap();
+        assert_eq!(decoded.len(), points.len());
+        let json = serde_json::to_value(&path).unwrap();
+        assert!(json["polyline"].is_string());
+        assert!(serde_json::to_value(straight_path()).unwrap().get("polyline").is_none());
+
+        path.decode().unwrap();
+        assert!(path.polyline.is_none());
+        assert_eq!(path.path.len(), points.len());
+        for (point, expected) in path.path.iter().zip(points.iter()) {
+            assert!((point.lat - expected.lat).abs() < 1e-5 && (point.lng - expected.lng).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_path_without_legs() {
+        let json = r#"{"title": "trip", "path": [], "distance": 0.0, "locations": []}"#;
+        let path: Path = serde_json::from_str(json).unwrap();
+        assert!(path.legs.is_empty());
+    }
 }
\ No newline at end of file
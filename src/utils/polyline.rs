@@ -0,0 +1,88 @@
+use crate::utils::coordinate::Coordinate;
+use std::error::Error;
+
+// Google encoded polyline format with 5 decimal places
+const PRECISION: f64 = 1e5;
+
+fn encode_value(value: i64, encoded: &mut String) {
+    let mut value = if value < 0 { !(value << 1) } else { value << 1 };
+    while value >= 0x20 {
+        encoded.push(char::from((((value & 0x1f) | 0x20) + 63) as u8));
+        value >>= 5;
+    }
+    encoded.push(char::from((value + 63) as u8));
+}
+
+pub fn encode(points: &[Coordinate]) -> String {
+    let mut encoded = String::new();
+    let (mut last_lat, mut last_lng) = (0, 0);
+    for point in points {
+        let lat = (point.lat * PRECISION).round() as i64;
+        let lng = (point.lng * PRECISION).round() as i64;
+        encode_value(lat - last_lat, &mut encoded);
+        encode_value(lng - last_lng, &mut encoded);
+        (last_lat, last_lng) = (lat, lng);
+    }
+    encoded
+}
+
+fn decode_value(bytes: &mut impl Iterator<Item = u8>) -> Result<i64, Box<dyn Error>> {
+    let mut value: i64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes.next().ok_or("Truncated polyline")?;
+        if !(63..127).contains(&byte) || shift > 60 {
+            return Err("Invalid polyline".into());
+        }
+        let chunk = i64::from(byte - 63);
+        value |= (chunk & 0x1f) << shift;
+        shift += 5;
+        if chunk < 0x20 {
+            break;
+        }
+    }
+    Ok(if value & 1 == 1 {
+        !(value >> 1)
+    } else {
+        value >> 1
+    })
+}
+
+pub fn decode(encoded: &str) -> Result<Vec<Coordinate>, Box<dyn Error>> {
+    let mut bytes = encoded.bytes().peekable();
+    let mut points = vec![];
+    let (mut lat, mut lng) = (0, 0);
+    while bytes.peek().is_some() {
+        lat += decode_value(&mut bytes)?;
+        lng += decode_value(&mut bytes)?;
+        points.push(Coordinate {
+            lat: lat as f64 / PRECISION,
+            lng: lng as f64 / PRECISION,
+            id: 0,
+        });
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_and_decode() {
+        // example from the format documentation
+        let points: Vec<Coordinate> = [(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)]
+            .iter()
+            .map(|&(lat, lng)| Coordinate { lat, lng, id: 0 })
+            .collect();
+        let encoded = encode(&points);
+        assert_eq!(encoded, "_p~iF~ps|U_ulLnnqC_mqNvxq`@");
+        let decoded = decode(&encoded).unwrap();
+        for (a, b) in points.iter().zip(decoded.iter()) {
+            assert!((a.lat - b.lat).abs() < 1e-9 && (a.lng - b.lng).abs() < 1e-9);
+        }
+        assert!(decode("").unwrap().is_empty());
+        assert!(decode("_p~iF").is_err());
+        assert!(decode("_p~iF~ps|").is_err());
+    }
+}
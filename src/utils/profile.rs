@@ -0,0 +1,60 @@
+use rocket::serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum RoutingProfile {
+    #[default]
+    Car,
+    Bicycle,
+    Walking,
+}
+
+impl RoutingProfile {
+    pub const ALL: [RoutingProfile; 3] = [
+        RoutingProfile::Car,
+        RoutingProfile::Bicycle,
+        RoutingProfile::Walking,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoutingProfile::Car => "car",
+            RoutingProfile::Bicycle => "bicycle",
+            RoutingProfile::Walking => "walking",
+        }
+    }
+
+    // typical speed in m/s, used to turn a travel time into a distance along the arcs
+    pub fn speed(&self) -> f64 {
+        match self {
+            RoutingProfile::Car => 13.9,
+            RoutingProfile::Bicycle => 4.2,
+            RoutingProfile::Walking => 1.4,
+        }
+    }
+
+    // prefix of the environment variables naming the files of this profile,
+    // e.g. BICYCLE_ARCS_FILE
+    pub fn env_prefix(&self) -> &'static str {
+        match self {
+            RoutingProfile::Car => "",
+            RoutingProfile::Bicycle => "BICYCLE_",
+            RoutingProfile::Walking => "WALKING_",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_serialization() {
+        for profile in RoutingProfile::ALL {
+            let json = serde_json::to_string(&profile).unwrap();
+            assert_eq!(json, format!("\"{}\"", profile.as_str()));
+            assert_eq!(serde_json::from_str::<RoutingProfile>(&json).unwrap(), profile);
+        }
+        assert!(serde_json::from_str::<RoutingProfile>("\"truck\"").is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use rocket::serde::Serialize;
+use crate::algo::error::RoutingError;
+use rocket::{http::Status, response::status::Custom, serde::{json::Json, Serialize}};
 
 #[derive(Serialize)]
 pub struct OkResponse {
@@ -10,6 +11,39 @@ pub struct OkResponse {
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub message: String,
+    // machine readable reason, see RoutingError::code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+}
+
+impl From<RoutingError> for Custom<Json<ErrorResponse>> {
+    fn from(error: RoutingError) -> Self {
+        let status = match error {
+            RoutingError::NoPath
+            | RoutingError::UnreachableLeg { .. }
+            | RoutingError::SnapFailed { .. }
+            | RoutingError::MatchFailed(_) => Status::UnprocessableEntity,
+            RoutingError::SolverTimeout => Status::ServiceUnavailable,
+            RoutingError::NodeOutOfRange(_)
+            | RoutingError::TooManyStops { .. }
+            | RoutingError::TooFewStops { .. }
+            | RoutingError::InvalidCoordinate { .. }
+            | RoutingError::ProfileUnavailable(_)
+            | RoutingError::ElevationUnavailable
+            | RoutingError::InvalidClimbPenalty
+            | RoutingError::TooManySources { .. }
+            | RoutingError::TooManyEntries { .. }
+            | RoutingError::TooManyAlternatives { .. }
+            | RoutingError::InvalidBudget { .. }
+            | RoutingError::InvalidTrace { .. }
+            | RoutingError::InvalidSigma => Status::BadRequest,
+        };
+        let response = ErrorResponse {
+            message: error.to_string(),
+            code: Some(error.code()),
+        };
+        Custom(status, Json(response))
+    }
 }
 
 #[derive(Serialize, Debug)]
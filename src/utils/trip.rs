@@ -1,5 +1,7 @@
 use rocket::serde::{Deserialize, Serialize};
 use crate::utils::coordinate::Coordinate;
+use crate::utils::{language::Language, path::GeometryFormat, profile::RoutingProfile};
+use chrono::NaiveTime;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(crate = "rocket::serde")]
@@ -17,4 +19,27 @@ pub struct Trip {
     pub back_to_start: bool,
     pub title: String,
     pub locations: Vec<Location>,
+    #[serde(default)]
+    pub profile: RoutingProfile,
+    // polygons the route must not enter
+    #[serde(default)]
+    pub avoid_areas: Vec<Vec<Coordinate>>,
+    // node pairs of closed road segments
+    #[serde(default)]
+    pub blocked_segments: Vec<(usize, usize)>,
+    // when given, legs are timed from this departure time
+    #[serde(default)]
+    pub start_time: Option<NaiveTime>,
+    // simplification tolerance of the route geometry, in metres
+    #[serde(default)]
+    pub tolerance: Option<f64>,
+    #[serde(default)]
+    pub geometry: GeometryFormat,
+    // language of the turn-by-turn instructions
+    #[serde(default)]
+    pub language: Language,
+    // extra cost per metre climbed, needs ELEVATION_FILE; the reported costs
+    // include it, the distances do not
+    #[serde(default)]
+    pub climb_penalty: Option<f64>,
 }
\ No newline at end of file